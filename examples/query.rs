@@ -1,6 +1,6 @@
 use std::collections::BTreeSet;
 
-use mouse_configurator::{enumerate, Event, HpMouse};
+use mouse_configurator::{button::describe_action, enumerate, Event, HpMouse};
 
 fn hp_mouse(mouse: HpMouse) {
     println!("Found HP mouse");
@@ -25,7 +25,7 @@ fn hp_mouse(mouse: HpMouse) {
                         "        id: {}, press_type: {:?}, action: {:?}",
                         button.id,
                         button.press_type,
-                        button.decode_action()
+                        button.decode_action().map(|ops| describe_action(&ops))
                     );
                 }
             }
@@ -0,0 +1,155 @@
+//! Log of battery levels reported by each mouse, used to estimate how long
+//! the battery will last.
+//!
+//! Readings are appended to `battery-history/SERIAL.csv` in the app data
+//! directory, one `TIMESTAMP,LEVEL` line each, with the time in seconds since
+//! the Unix epoch.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::config::{app_data_dir, encode_serial, write_atomic};
+
+/// Readings older than this are discarded
+pub const HISTORY_DURATION: Duration = Duration::from_secs(14 * 24 * 60 * 60);
+
+// Minimum drop in level to estimate a discharge rate from
+const MIN_ESTIMATE_DROP: u8 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reading {
+    /// Seconds since the Unix epoch
+    pub time: u64,
+    pub level: u8,
+}
+
+impl Reading {
+    fn parse(line: &str) -> Option<Self> {
+        let (time, level) = line.split_once(',')?;
+        Some(Self {
+            time: time.trim().parse().ok()?,
+            level: level.trim().parse().ok()?,
+        })
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |x| x.as_secs())
+}
+
+fn history_path(serial: &str) -> PathBuf {
+    let mut path = app_data_dir();
+    path.push("battery-history");
+    path.push(format!("{}.csv", encode_serial(serial)));
+    path
+}
+
+/// Append a reading for `serial`
+pub fn record(serial: &str, level: u8) -> io::Result<()> {
+    let path = history_path(serial);
+    fs::create_dir_all(path.parent().unwrap())?;
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{},{}", now(), level)
+}
+
+/// Load readings for `serial` from the last `HISTORY_DURATION`, oldest first.
+/// Older readings are removed from the file.
+pub fn load(serial: &str) -> io::Result<Vec<Reading>> {
+    let path = history_path(serial);
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+    let mut readings = Vec::new();
+    for line in BufReader::new(file).lines() {
+        // Ignore partially written lines
+        if let Some(reading) = Reading::parse(&line?) {
+            readings.push(reading);
+        }
+    }
+    // The clock may have been set back, or the file edited
+    readings.sort_by_key(|x| x.time);
+
+    let cutoff = now().saturating_sub(HISTORY_DURATION.as_secs());
+    let len = readings.len();
+    readings.retain(|x| x.time >= cutoff);
+    if readings.len() != len {
+        let mut data = String::new();
+        for reading in &readings {
+            data.push_str(&format!("{},{}\n", reading.time, reading.level));
+        }
+        write_atomic(&path, &data)?;
+    }
+
+    Ok(readings)
+}
+
+/// Estimate time until the battery is empty, from the discharge rate since
+/// the mouse was last charged
+pub fn estimate_remaining(readings: &[Reading]) -> Option<Duration> {
+    let last = readings.last()?;
+    // Find the start of the current discharge; the level only drops
+    let mut first = last;
+    for reading in readings.iter().rev().skip(1) {
+        if reading.level < first.level {
+            break;
+        }
+        first = reading;
+    }
+
+    let drop = first.level - last.level;
+    let elapsed = last.time.checked_sub(first.time)?;
+    if drop < MIN_ESTIMATE_DROP || elapsed == 0 {
+        return None;
+    }
+    let secs_per_percent = elapsed as f64 / f64::from(drop);
+    Some(Duration::from_secs_f64(
+        secs_per_percent * f64::from(last.level),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn readings(levels: &[(u64, u8)]) -> Vec<Reading> {
+        levels
+            .iter()
+            .map(|&(time, level)| Reading { time, level })
+            .collect()
+    }
+
+    #[test]
+    fn parse_reading() {
+        assert_eq!(
+            Reading::parse("1650000000,87"),
+            Some(Reading {
+                time: 1650000000,
+                level: 87
+            })
+        );
+        assert_eq!(Reading::parse("1650000000,"), None);
+    }
+
+    #[test]
+    fn estimate() {
+        // 10% per hour, from the last charge
+        let history = readings(&[(0, 40), (3600, 20), (7200, 100), (10800, 90), (14400, 80)]);
+        assert_eq!(
+            estimate_remaining(&history),
+            Some(Duration::from_secs(8 * 3600))
+        );
+
+        // Not enough change
+        let history = readings(&[(0, 50), (3600, 49)]);
+        assert_eq!(estimate_remaining(&history), None);
+        assert_eq!(estimate_remaining(&[]), None);
+    }
+}
@@ -3,10 +3,12 @@
 
 use once_cell::sync::Lazy;
 use serde::de::{self, Error};
-use std::collections::HashMap;
+use std::{collections::HashMap, str::FromStr};
 
-use crate::keycode::*;
-use mouse_configurator::{Op, Value::*};
+use crate::{
+    keycode::{Consumer, Key, Modifiers},
+    Op,
+};
 
 // TODO better naming? Important if serialized in json.
 #[repr(u8)]
@@ -55,6 +57,18 @@ impl HardwareButton {
         }
     }
 
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Right => "right",
+            Self::Middle => "middle",
+            Self::LeftBottom => "left-bottom",
+            Self::LeftTop => "left-top",
+            Self::ScrollLeft => "scroll-left",
+            Self::ScrollRight => "scroll-right",
+            Self::LeftCenter => "left-center",
+        }
+    }
+
     pub fn def_binding(self) -> &'static Entry {
         match self {
             Self::Right => PresetBinding::RightClick,
@@ -69,6 +83,20 @@ impl HardwareButton {
     }
 }
 
+// Parse name, or index
+impl FromStr for HardwareButton {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        if let Some(button) = s.parse().ok().and_then(Self::from_u8) {
+            return Ok(button);
+        }
+        Self::iter()
+            .find(|x| x.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("Unknown button `{}`", s))
+    }
+}
+
 pub struct Category {
     pub label: &'static str,
     pub entries: Vec<Entry>,
@@ -206,37 +234,46 @@ pub static BINDINGS: Lazy<Vec<Category>> = Lazy::new(|| {
                 Entry {
                     id: VolumeDown,
                     label: "Volume Down",
-                    binding: vec![Op::media(true, vec![Const(MEDIA_VolumeDown)])],
+                    binding: vec![Op::media(
+                        true,
+                        vec![Consumer::VolumeDown.payload().unwrap()],
+                    )],
                     keybind: None,
                 },
                 Entry {
                     id: VolumeUp,
                     label: "Volume Up",
-                    binding: vec![Op::media(true, vec![Const(MEDIA_VolumeUp)])],
+                    binding: vec![Op::media(true, vec![Consumer::VolumeUp.payload().unwrap()])],
                     keybind: None,
                 },
                 Entry {
                     id: NextTrack,
                     label: "Next Track",
-                    binding: vec![Op::media(true, vec![Const(MEDIA_NextSong)])],
+                    binding: vec![Op::media(true, vec![Consumer::NextSong.payload().unwrap()])],
                     keybind: None,
                 },
                 Entry {
                     id: PreviousTrack,
                     label: "Previous Track",
-                    binding: vec![Op::media(true, vec![Const(MEDIA_PreviousSong)])],
+                    binding: vec![Op::media(
+                        true,
+                        vec![Consumer::PreviousSong.payload().unwrap()],
+                    )],
                     keybind: None,
                 },
                 Entry {
                     id: PlayPause,
                     label: "Play / Pause",
-                    binding: vec![Op::media(true, vec![Const(MEDIA_PlayPause)])],
+                    binding: vec![Op::media(
+                        true,
+                        vec![Consumer::PlayPause.payload().unwrap()],
+                    )],
                     keybind: None,
                 },
                 Entry {
                     id: Mute,
                     label: "Mute",
-                    binding: vec![Op::media(true, vec![Const(MEDIA_Mute)])],
+                    binding: vec![Op::media(true, vec![Consumer::Mute.payload().unwrap()])],
                     keybind: None,
                 },
             ],
@@ -247,43 +284,64 @@ pub static BINDINGS: Lazy<Vec<Category>> = Lazy::new(|| {
                 Entry {
                     id: Copy,
                     label: "Copy",
-                    binding: vec![Op::key(true, vec![Const(MOD_Ctrl), Const(KEY_C)])],
+                    binding: vec![Op::key(
+                        true,
+                        vec![Modifiers::CTRL.payload(), Key::C.payload()],
+                    )],
                     keybind: Some("Ctrl+C"),
                 },
                 Entry {
                     id: Cut,
                     label: "Cut",
-                    binding: vec![Op::key(true, vec![Const(MOD_Ctrl), Const(KEY_X)])],
+                    binding: vec![Op::key(
+                        true,
+                        vec![Modifiers::CTRL.payload(), Key::X.payload()],
+                    )],
                     keybind: Some("Ctrl+X"),
                 },
                 Entry {
                     id: Paste,
                     label: "Paste",
-                    binding: vec![Op::key(true, vec![Const(MOD_Ctrl), Const(KEY_V)])],
+                    binding: vec![Op::key(
+                        true,
+                        vec![Modifiers::CTRL.payload(), Key::V.payload()],
+                    )],
                     keybind: Some("Ctrl+V"),
                 },
                 Entry {
                     id: Undo,
                     label: "Undo",
-                    binding: vec![Op::key(true, vec![Const(MOD_Ctrl), Const(KEY_Z)])],
+                    binding: vec![Op::key(
+                        true,
+                        vec![Modifiers::CTRL.payload(), Key::Z.payload()],
+                    )],
                     keybind: Some("Ctrl+Z"),
                 },
                 Entry {
                     id: Redo,
                     label: "Redo",
-                    binding: vec![Op::key(true, vec![Const(MOD_Ctrl), Const(KEY_Y)])],
+                    binding: vec![Op::key(
+                        true,
+                        vec![Modifiers::CTRL.payload(), Key::Y.payload()],
+                    )],
                     keybind: Some("Ctrl+Y"),
                 },
                 Entry {
                     id: SelectAll,
                     label: "Select All",
-                    binding: vec![Op::key(true, vec![Const(MOD_Ctrl), Const(KEY_A)])],
+                    binding: vec![Op::key(
+                        true,
+                        vec![Modifiers::CTRL.payload(), Key::A.payload()],
+                    )],
                     keybind: Some("Ctrl+A"),
                 },
                 Entry {
                     id: Find,
                     label: "Find",
-                    binding: vec![Op::key(true, vec![Const(MOD_Ctrl), Const(KEY_F)])],
+                    binding: vec![Op::key(
+                        true,
+                        vec![Modifiers::CTRL.payload(), Key::F.payload()],
+                    )],
                     keybind: Some("Ctrl+F"),
                 },
             ],
@@ -294,37 +352,55 @@ pub static BINDINGS: Lazy<Vec<Category>> = Lazy::new(|| {
                 Entry {
                     id: Refresh,
                     label: "Refresh",
-                    binding: vec![Op::key(true, vec![Const(MOD_Ctrl), Const(KEY_R)])],
+                    binding: vec![Op::key(
+                        true,
+                        vec![Modifiers::CTRL.payload(), Key::R.payload()],
+                    )],
                     keybind: Some("Ctrl+R"),
                 },
                 Entry {
                     id: OpenTab,
                     label: "Open New Tab/Page",
-                    binding: vec![Op::key(true, vec![Const(MOD_Ctrl), Const(KEY_T)])],
+                    binding: vec![Op::key(
+                        true,
+                        vec![Modifiers::CTRL.payload(), Key::T.payload()],
+                    )],
                     keybind: Some("Ctrl+T"),
                 },
                 Entry {
                     id: CloseTab,
                     label: "Close Current Tab/Page",
-                    binding: vec![Op::key(true, vec![Const(MOD_Ctrl), Const(KEY_W)])],
+                    binding: vec![Op::key(
+                        true,
+                        vec![Modifiers::CTRL.payload(), Key::W.payload()],
+                    )],
                     keybind: Some("Ctrl+W"),
                 },
                 Entry {
                     id: NewWindow,
                     label: "New Window",
-                    binding: vec![Op::key(true, vec![Const(MOD_Ctrl), Const(KEY_N)])],
+                    binding: vec![Op::key(
+                        true,
+                        vec![Modifiers::CTRL.payload(), Key::N.payload()],
+                    )],
                     keybind: Some("Ctrl+N"),
                 },
                 Entry {
                     id: Home,
                     label: "Home",
-                    binding: vec![Op::key(true, vec![Const(MOD_Alt), Const(KEY_Home)])],
+                    binding: vec![Op::key(
+                        true,
+                        vec![Modifiers::ALT.payload(), Key::Home.payload()],
+                    )],
                     keybind: Some("Alt+Home"),
                 },
                 Entry {
                     id: SaveBookmark,
                     label: "Save Page as Bookmark",
-                    binding: vec![Op::key(true, vec![Const(MOD_Ctrl), Const(KEY_D)])],
+                    binding: vec![Op::key(
+                        true,
+                        vec![Modifiers::CTRL.payload(), Key::D.payload()],
+                    )],
                     keybind: Some("Ctrl+D"),
                 },
                 Entry {
@@ -332,14 +408,20 @@ pub static BINDINGS: Lazy<Vec<Category>> = Lazy::new(|| {
                     label: "Zoom In",
                     binding: vec![Op::key(
                         true,
-                        vec![Const(MOD_Ctrl | MOD_Shift), Const(KEY_Equal)],
+                        vec![
+                            (Modifiers::CTRL | Modifiers::SHIFT).payload(),
+                            Key::Equal.payload(),
+                        ],
                     )],
                     keybind: Some("Ctrl +"),
                 },
                 Entry {
                     id: ZoomOut,
                     label: "Zoom Out",
-                    binding: vec![Op::key(true, vec![Const(MOD_Ctrl), Const(KEY_Minus)])],
+                    binding: vec![Op::key(
+                        true,
+                        vec![Modifiers::CTRL.payload(), Key::Minus.payload()],
+                    )],
                     keybind: Some("Ctrl -"),
                 },
             ],
@@ -352,7 +434,10 @@ pub static BINDINGS: Lazy<Vec<Category>> = Lazy::new(|| {
                     label: "Move To Workspace Below",
                     binding: vec![Op::key(
                         true,
-                        vec![Const(MOD_Ctrl | MOD_Super), Const(KEY_Down)],
+                        vec![
+                            (Modifiers::CTRL | Modifiers::SUPER).payload(),
+                            Key::Down.payload(),
+                        ],
                     )],
                     keybind: Some("Ctrl+Super+Down"),
                 },
@@ -361,20 +446,29 @@ pub static BINDINGS: Lazy<Vec<Category>> = Lazy::new(|| {
                     label: "Move To Workspace Above",
                     binding: vec![Op::key(
                         true,
-                        vec![Const(MOD_Ctrl | MOD_Super), Const(KEY_Up)],
+                        vec![
+                            (Modifiers::CTRL | Modifiers::SUPER).payload(),
+                            Key::Up.payload(),
+                        ],
                     )],
                     keybind: Some("Ctrl+Super+Up"),
                 },
                 Entry {
                     id: WorkspacesOverview,
                     label: "Workspaces Overview",
-                    binding: vec![Op::key(true, vec![Const(MOD_Super), Const(KEY_D)])],
+                    binding: vec![Op::key(
+                        true,
+                        vec![Modifiers::SUPER.payload(), Key::D.payload()],
+                    )],
                     keybind: Some("Ctrl+D"),
                 },
                 Entry {
                     id: ToggleFullscreen,
                     label: "Toggle Fullscreen",
-                    binding: vec![Op::key(true, vec![Const(0), Const(KEY_F11)])],
+                    binding: vec![Op::key(
+                        true,
+                        vec![Modifiers::empty().payload(), Key::F11.payload()],
+                    )],
                     keybind: Some("F11"),
                 },
                 Entry {
@@ -382,56 +476,83 @@ pub static BINDINGS: Lazy<Vec<Category>> = Lazy::new(|| {
                     label: "Log Out",
                     binding: vec![Op::key(
                         true,
-                        vec![Const(MOD_Ctrl | MOD_Alt), Const(KEY_Delete)],
+                        vec![
+                            (Modifiers::CTRL | Modifiers::ALT).payload(),
+                            Key::Delete.payload(),
+                        ],
                     )],
                     keybind: Some("Ctrl+Alt+Delete"),
                 },
                 Entry {
                     id: LockScreen,
                     label: "Lock Screen",
-                    binding: vec![Op::key(true, vec![Const(MOD_Super), Const(KEY_Esc)])],
+                    binding: vec![Op::key(
+                        true,
+                        vec![Modifiers::SUPER.payload(), Key::Esc.payload()],
+                    )],
                     keybind: Some("Super+Escape"),
                 },
                 Entry {
                     id: Super,
                     label: "Super",
-                    binding: vec![Op::key(true, vec![Const(0), Const(KEY_LeftSuper)])],
+                    binding: vec![Op::key(
+                        true,
+                        vec![Modifiers::empty().payload(), Key::LeftSuper.payload()],
+                    )],
                     keybind: None,
                 },
                 Entry {
                     id: PrintScreen,
                     label: "Print Screen",
-                    binding: vec![Op::key(true, vec![Const(0), Const(KEY_SysRq)])],
+                    binding: vec![Op::key(
+                        true,
+                        vec![Modifiers::empty().payload(), Key::SysRq.payload()],
+                    )],
                     keybind: None,
                 },
                 Entry {
                     id: ShowApplications,
                     label: "Show All Applications",
-                    binding: vec![Op::key(true, vec![Const(MOD_Super), Const(KEY_A)])],
+                    binding: vec![Op::key(
+                        true,
+                        vec![Modifiers::SUPER.payload(), Key::A.payload()],
+                    )],
                     keybind: Some("Super+A"),
                 },
                 Entry {
                     id: SwitchApplications,
                     label: "Switch Applications",
-                    binding: vec![Op::key(true, vec![Const(MOD_Super), Const(KEY_Tab)])],
+                    binding: vec![Op::key(
+                        true,
+                        vec![Modifiers::SUPER.payload(), Key::Tab.payload()],
+                    )],
                     keybind: Some("Super+Tab"),
                 },
                 Entry {
                     id: ShowLauncher,
                     label: "Open Launcher",
-                    binding: vec![Op::key(true, vec![Const(MOD_Super), Const(KEY_Slash)])],
+                    binding: vec![Op::key(
+                        true,
+                        vec![Modifiers::SUPER.payload(), Key::Slash.payload()],
+                    )],
                     keybind: Some("Super+/"),
                 },
                 Entry {
                     id: BrightnessUp,
                     label: "Increase Display Brightness",
-                    binding: vec![Op::media(true, vec![Const(MEDIA_BrightnessUp)])],
+                    binding: vec![Op::media(
+                        true,
+                        vec![Consumer::BrightnessUp.payload().unwrap()],
+                    )],
                     keybind: None,
                 },
                 Entry {
                     id: BrightnessDown,
                     label: "Decrease Display Brightness",
-                    binding: vec![Op::media(true, vec![Const(MEDIA_BrightnessDown)])],
+                    binding: vec![Op::media(
+                        true,
+                        vec![Consumer::BrightnessDown.payload().unwrap()],
+                    )],
                     keybind: None,
                 },
             ],
@@ -456,7 +577,7 @@ impl Entry {
 
 #[cfg(test)]
 mod tests {
-    use mouse_configurator::button::{decode_action, encode_action};
+    use crate::button::{decode_action, encode_action};
 
     use super::*;
 
@@ -471,4 +592,13 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn parse_hardware_button() {
+        for button in HardwareButton::iter() {
+            assert_eq!(button.name().parse(), Ok(button));
+            assert_eq!((button as u8).to_string().parse(), Ok(button));
+        }
+        assert!("7".parse::<HardwareButton>().is_err());
+    }
 }
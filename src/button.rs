@@ -1,6 +1,8 @@
 use bitvec::prelude::*;
 use std::fmt;
 
+use crate::keycode::{Consumer, KeyCombination};
+
 pub struct BitStream<'a> {
     bits: &'a BitSlice<u8, Lsb0>,
 }
@@ -71,13 +73,22 @@ impl<T: fmt::Debug> fmt::Debug for Value<T> {
     }
 }
 
+impl<T: fmt::Display> fmt::Display for Value<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Var(var) => write!(f, "var{}", var),
+            Self::Const(val) => write!(f, "{}", val),
+        }
+    }
+}
+
 impl Default for Value<i16> {
     fn default() -> Self {
         Self::Const(0)
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Op {
     Kill,
     Pause(Value<i16>),
@@ -137,6 +148,139 @@ impl Op {
     }
 }
 
+const MOUSE_BUTTONS: &[(i16, &str)] = &[
+    (1, "Left"),
+    (2, "Right"),
+    (4, "Middle"),
+    (8, "Back"),
+    (16, "Forward"),
+];
+
+fn fmt_mouse_buttons(f: &mut fmt::Formatter<'_>, buttons: i16) -> fmt::Result {
+    let mut first = true;
+    for (mask, label) in MOUSE_BUTTONS {
+        if buttons & mask != 0 {
+            if !first {
+                f.write_str("+")?;
+            }
+            f.write_str(label)?;
+            first = false;
+        }
+    }
+    let unknown = buttons & !MOUSE_BUTTONS.iter().fold(0, |acc, (mask, _)| acc | mask);
+    if unknown != 0 {
+        if !first {
+            f.write_str("+")?;
+        }
+        write!(f, "{:#X}", unknown)?;
+    }
+    Ok(())
+}
+
+fn fmt_media_payload(f: &mut fmt::Formatter<'_>, payload: &[Value<i8>]) -> fmt::Result {
+    for (i, value) in payload.iter().enumerate() {
+        if i != 0 {
+            f.write_str("+")?;
+        }
+        match value {
+            Value::Const(usage) => match Consumer::from_usage(u16::from(*usage as u8)) {
+                Some(usage) => write!(f, "{}", usage)?,
+                None => write!(f, "Usage {:#04X}", *usage as u8)?,
+            },
+            Value::Var(_) => write!(f, "{}", value)?,
+        }
+    }
+    Ok(())
+}
+
+/// Human readable description, like "Ctrl+C" or "Press Left"
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Kill => f.write_str("Stop"),
+            Self::Pause(value) => write!(f, "Pause {}", value),
+            Self::Mouse {
+                auto_release,
+                buttons,
+                dx,
+                dy,
+                wheel1,
+                wheel2,
+            } => {
+                let zero = Value::Const(0);
+                let mut parts = 0;
+                let mut sep = |f: &mut fmt::Formatter<'_>| {
+                    parts += 1;
+                    if parts > 1 {
+                        f.write_str(", ")
+                    } else {
+                        Ok(())
+                    }
+                };
+                if *buttons != zero {
+                    sep(f)?;
+                    f.write_str(if *auto_release { "Click " } else { "Press " })?;
+                    match buttons {
+                        Value::Const(buttons) => fmt_mouse_buttons(f, *buttons)?,
+                        Value::Var(_) => write!(f, "{}", buttons)?,
+                    }
+                }
+                if *dx != zero || *dy != zero {
+                    sep(f)?;
+                    write!(f, "Move ({}, {})", dx, dy)?;
+                }
+                if *wheel1 != zero {
+                    sep(f)?;
+                    write!(f, "Scroll {}", wheel1)?;
+                }
+                if *wheel2 != zero {
+                    sep(f)?;
+                    write!(f, "Scroll Horizontally {}", wheel2)?;
+                }
+                if parts == 0 {
+                    f.write_str("Release Mouse Buttons")?;
+                }
+                Ok(())
+            }
+            Self::Key {
+                auto_release,
+                payload,
+            } => {
+                if payload.is_empty() {
+                    return f.write_str("Release Keys");
+                }
+                if !auto_release {
+                    f.write_str("Press ")?;
+                }
+                match KeyCombination::from_payload(payload) {
+                    Some(combination) => write!(f, "{}", combination),
+                    None => write!(f, "Keys {:?}", payload),
+                }
+            }
+            Self::Media {
+                auto_release,
+                payload,
+            } => {
+                if payload.is_empty() {
+                    return f.write_str("Release Media Keys");
+                }
+                if !auto_release {
+                    f.write_str("Press ")?;
+                }
+                fmt_media_payload(f, payload)
+            }
+        }
+    }
+}
+
+/// Describe a sequence of operations, as decoded by `decode_action`
+pub fn describe_action(ops: &[Op]) -> String {
+    ops.iter()
+        .map(|op| op.to_string())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
 fn get_payload(bitstream: &mut BitStream) -> Result<Vec<Value<i8>>, &'static str> {
     let mut values = Vec::new();
     loop {
@@ -407,7 +551,7 @@ impl PressType {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Button {
     pub id: u8,
     pub host_id: u8,
@@ -425,6 +569,16 @@ impl Button {
         }
     }
 
+    /// Create with an already encoded action, such as one read from a device
+    pub fn new_raw(id: u8, host_id: u8, press_type: PressType, action: Vec<u8>) -> Self {
+        Self {
+            id,
+            host_id,
+            press_type,
+            action,
+        }
+    }
+
     pub fn decode(data: &[u8]) -> Option<(Self, usize)> {
         if data.len() <= 3 {
             // Buffer too small
@@ -452,6 +606,10 @@ impl Button {
     pub fn decode_action(&self) -> Result<Vec<Op>, String> {
         decode_action(&self.action)
     }
+
+    pub fn action(&self) -> &[u8] {
+        &self.action
+    }
 }
 
 #[cfg(test)]
@@ -519,6 +677,24 @@ mod tests {
         assert_eq!(decode_action(bytes).unwrap(), zoom_out);
     }
 
+    #[test]
+    fn test_describe_action() {
+        assert_eq!(
+            describe_action(&zoom_in()),
+            "Press Ctrl; Pause 100; Scroll 1; Pause 100; Release Keys"
+        );
+        #[allow(overflowing_literals)]
+        let copy = vec![Op::key(true, vec![Const(1), Const(0x06)])];
+        assert_eq!(describe_action(&copy), "Ctrl+C");
+        #[allow(overflowing_literals)]
+        let play_pause = vec![Op::media(true, vec![Const(0xCD)])];
+        assert_eq!(describe_action(&play_pause), "Play/Pause");
+        assert_eq!(
+            describe_action(&[Op::mouse(true, 2, 0, 0, 0, 0)]),
+            "Click Right"
+        );
+    }
+
     #[test]
     fn test_media_play_pause() {
         #[allow(overflowing_literals)]
@@ -0,0 +1,52 @@
+//! Background mode, which applies the saved configuration to each known mouse
+//! when it connects. Intended to run as a systemd user service.
+
+use std::{path::PathBuf, thread};
+
+use super::{Device, Error};
+use mouse_configurator::{config::load_config, enumerate, monitor, MonitorEvent};
+
+// Apply the configuration for the device at `path`, if it has one. Errors are
+// logged, since they only affect one device.
+fn apply(path: PathBuf) {
+    let device = match Device::open(&path) {
+        Ok(device) => device,
+        Err(err) => {
+            eprintln!("{}", err);
+            return;
+        }
+    };
+    // Load on each connection, to use changes saved since the daemon started
+    let (configs, errors) = load_config();
+    for err in &errors {
+        eprintln!("{}", err);
+    }
+    let config = match configs.get(&device.serial) {
+        Some(config) => config,
+        None => {
+            eprintln!("No saved configuration for `{}`", device.serial);
+            return;
+        }
+    };
+    match device.apply_config(config) {
+        Ok(()) => eprintln!("Applied configuration to `{}`", device.serial),
+        Err(err) => eprintln!(
+            "Failed to apply configuration to `{}`: {}",
+            device.serial, err
+        ),
+    }
+}
+
+pub fn run() -> Result<(), Error> {
+    // Start monitoring first, so devices added while enumerating aren't missed
+    let monitor = monitor()?;
+    let added = monitor.filter_map(|event| match event {
+        MonitorEvent::Added(info) => Some(info),
+        _ => None,
+    });
+    for info in enumerate()?.into_iter().chain(added) {
+        // Waiting for the device to respond shouldn't block others
+        thread::spawn(move || apply(info.devnode));
+    }
+    Err(Error::Failed("Device monitor stopped".to_string()))
+}
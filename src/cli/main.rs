@@ -0,0 +1,568 @@
+//! Command line interface, for configuring mice without a display.
+//!
+//! Settings are written to the device and to the saved configuration, so the
+//! GUI applies the same settings when it next sees the device.
+
+use nix::{
+    errno::Errno,
+    poll::{poll, PollFd, PollFlags},
+};
+use serde_json::{json, Map, Value};
+use std::{
+    collections::HashMap,
+    env, fmt, io,
+    os::unix::io::AsRawFd,
+    path::{Path, PathBuf},
+    process,
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use mouse_configurator::{
+    bindings::HardwareButton,
+    config::{load_config, round_dpi, save_config, Binding, MouseConfig, Profile},
+    enumerate,
+    policy::policy,
+    Event, HpMouse, HpMouseEvents, ReadRes,
+};
+
+mod daemon;
+
+const USAGE: &str = "Usage: mouse-configurator-cli [--json] [--device SERIAL] COMMAND
+
+Commands:
+  list                      List connected devices
+  info                      Show firmware, battery, DPI, and bindings
+  battery                   Show battery level
+  dpi get                   Show DPI
+  dpi set DPI               Set DPI of the active configuration
+  bind BUTTON ACTION        Bind a button to a preset like `volume-up`, a
+                            shortcut like `Ctrl+C`, or `default`
+  left-handed [on|off]      Show or set left handed mode
+  export FILE               Export saved configuration
+  import FILE               Import configuration, and apply it
+  apply-profile N           Select and apply configuration N
+  reset                     Reset device to defaults
+  daemon                    Apply saved configuration whenever a device
+                            connects
+
+Buttons: right, middle, left-bottom, left-top, scroll-left, scroll-right,
+left-center
+
+Options:
+  --json                    Print output as JSON
+  -d, --device SERIAL       Only use device with serial number SERIAL
+
+Exit status is 0 on success, 1 on failure, 2 for invalid usage, and 3 if no
+device was found. With `--json`, errors are printed to stderr as an object
+with an `error` message.";
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+enum Error {
+    Usage(String),
+    NoDevice,
+    Failed(String),
+}
+
+impl Error {
+    // Message, without usage
+    fn message(&self) -> String {
+        match self {
+            Self::Usage(msg) | Self::Failed(msg) => msg.clone(),
+            Self::NoDevice => self.to_string(),
+        }
+    }
+
+    fn exit_code(&self) -> i32 {
+        match self {
+            Self::Failed(_) => 1,
+            Self::Usage(_) => 2,
+            Self::NoDevice => 3,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Usage(msg) => write!(f, "{}\n\n{}", msg, USAGE),
+            Self::NoDevice => write!(f, "No device found"),
+            Self::Failed(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Self::Failed(err.to_string())
+    }
+}
+
+enum Command {
+    List,
+    Info,
+    Battery,
+    GetDpi,
+    SetDpi(u16),
+    Bind(HardwareButton, Binding),
+    LeftHanded(Option<bool>),
+    Export(PathBuf),
+    Import(PathBuf),
+    ApplyProfile(usize),
+    Reset,
+    Daemon,
+}
+
+impl Command {
+    fn parse(args: &[String]) -> Result<Self, Error> {
+        let usage = |msg: &str| Error::Usage(msg.to_string());
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        Ok(match args.as_slice() {
+            ["list"] => Self::List,
+            ["info"] => Self::Info,
+            ["battery"] => Self::Battery,
+            ["dpi", "get"] => Self::GetDpi,
+            ["dpi", "set", dpi] => {
+                let dpi = dpi.parse().map_err(|_| usage("Invalid DPI"))?;
+                Self::SetDpi(dpi)
+            }
+            ["bind", button, action] => {
+                let button: HardwareButton = button.parse().map_err(|err: String| usage(&err))?;
+                let binding = if *action == "default" {
+                    Binding::Preset(button.def_binding().id)
+                } else {
+                    Binding::parse(action).map_err(|err| usage(&err))?
+                };
+                Self::Bind(button, binding)
+            }
+            ["left-handed"] => Self::LeftHanded(None),
+            ["left-handed", "on"] => Self::LeftHanded(Some(true)),
+            ["left-handed", "off"] => Self::LeftHanded(Some(false)),
+            ["export", path] => Self::Export(path.into()),
+            ["import", path] => Self::Import(path.into()),
+            ["apply-profile", profile] => {
+                let profile = profile.parse().map_err(|_| usage("Invalid profile"))?;
+                Self::ApplyProfile(profile)
+            }
+            ["reset"] => Self::Reset,
+            ["daemon"] => Self::Daemon,
+            [] => return Err(usage("No command given")),
+            _ => return Err(usage(&format!("Invalid command `{}`", args.join(" ")))),
+        })
+    }
+
+    // Only one device can be exported to, or imported from, a file
+    fn single_device(&self) -> bool {
+        matches!(self, Self::Export(_) | Self::Import(_))
+    }
+
+    fn modifies_config(&self) -> bool {
+        matches!(
+            self,
+            Self::SetDpi(_)
+                | Self::Bind(..)
+                | Self::LeftHanded(Some(_))
+                | Self::Import(_)
+                | Self::ApplyProfile(_)
+                | Self::Reset
+        )
+    }
+}
+
+struct Device {
+    mouse: HpMouse,
+    events: HpMouseEvents,
+    device: String,
+    serial: String,
+    version: (u16, u16, u16),
+}
+
+impl Device {
+    fn open(path: &Path) -> Result<Self, Error> {
+        let mouse = HpMouse::open_devnode(path).map_err(|err| {
+            Error::Failed(format!("Failed to open `{}`: {}", path.display(), err))
+        })?;
+        let mut events = mouse.read();
+        mouse.query_firmware()?;
+        let (device, serial, version) = wait_for(&mut events, |event| match event {
+            Event::Firmware {
+                device,
+                serial,
+                version,
+            } => Some((device, serial, version)),
+            _ => None,
+        })?;
+        Ok(Self {
+            mouse,
+            events,
+            device,
+            serial,
+            version,
+        })
+    }
+
+    fn wait_for<T, F: FnMut(Event) -> Option<T>>(&mut self, f: F) -> Result<T, Error> {
+        wait_for(&mut self.events, f)
+    }
+
+    fn battery(&mut self) -> Result<u8, Error> {
+        self.mouse.query_battery()?;
+        self.wait_for(|event| match event {
+            Event::Battery { level, .. } => Some(level),
+            _ => None,
+        })
+    }
+
+    fn dpi_and_left_handed(&mut self) -> Result<(u16, bool), Error> {
+        self.mouse.query_dpi()?;
+        self.wait_for(|event| match event {
+            Event::Mouse {
+                dpi, left_handed, ..
+            } => Some((dpi, left_handed)),
+            _ => None,
+        })
+    }
+
+    fn bindings(&mut self) -> Result<Map<String, Value>, Error> {
+        self.mouse.query_button()?;
+        let (host_id, buttons) = self.wait_for(|event| match event {
+            Event::Buttons {
+                host_id, buttons, ..
+            } => Some((host_id, buttons)),
+            _ => None,
+        })?;
+        let mut bindings = Map::new();
+        for id in HardwareButton::iter() {
+            let label = match buttons
+                .iter()
+                .find(|x| x.host_id == host_id && x.id == id as u8)
+            {
+                Some(button) => Binding::for_button(button).label(),
+                None => id.def_binding().label.to_string(),
+            };
+            bindings.insert(id.name().to_string(), label.into());
+        }
+        Ok(bindings)
+    }
+
+    fn apply_profile(&self, profile: &Profile) -> Result<(), Error> {
+        let profile = policy().apply(profile);
+        for id in HardwareButton::iter() {
+            self.mouse.set_button(profile.button(id), false)?;
+        }
+        self.mouse.set_left_handed(profile.left_handed)?;
+        self.mouse.set_dpi(round_dpi(profile.dpi))?;
+        Ok(())
+    }
+
+    fn apply_config(&self, config: &MouseConfig) -> Result<(), Error> {
+        self.apply_profile(config.profile())
+    }
+}
+
+// Path of a device that failed to open, and the error
+type OpenError = (PathBuf, Error);
+
+// Open devices in parallel, so a device that doesn't respond doesn't delay the
+// others. Returns devices matching `serial`, in enumeration order, and
+// devices that failed to open.
+fn open_devices(serial: Option<&str>) -> Result<(Vec<Device>, Vec<OpenError>), Error> {
+    let (sender, receiver) = mpsc::channel();
+    for (i, info) in enumerate()?.into_iter().enumerate() {
+        let sender = sender.clone();
+        thread::spawn(move || {
+            let res = Device::open(&info.devnode);
+            let _ = sender.send((i, info.devnode, res));
+        });
+    }
+    drop(sender);
+
+    let mut devices = Vec::new();
+    let mut failed = Vec::new();
+    for (i, path, res) in receiver {
+        match res {
+            Ok(device) if serial.is_none() || serial == Some(device.serial.as_str()) => {
+                devices.push((i, device));
+                // Don't wait for other devices
+                if serial.is_some() {
+                    break;
+                }
+            }
+            Ok(_) => {}
+            Err(err) => failed.push((i, path, err)),
+        }
+    }
+    devices.sort_by_key(|(i, _)| *i);
+    failed.sort_by_key(|(i, _, _)| *i);
+    Ok((
+        devices.into_iter().map(|(_, device)| device).collect(),
+        failed
+            .into_iter()
+            .map(|(_, path, err)| (path, err))
+            .collect(),
+    ))
+}
+
+// Read events until `f` returns `Some`
+fn wait_for<T, F: FnMut(Event) -> Option<T>>(
+    events: &mut HpMouseEvents,
+    mut f: F,
+) -> Result<T, Error> {
+    let deadline = Instant::now() + TIMEOUT;
+    loop {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        let fd = PollFd::new(events.as_raw_fd(), PollFlags::POLLIN);
+        match poll(&mut [fd], timeout.as_millis() as i32) {
+            Ok(0) => return Err(Error::Failed("Timed out waiting for device".to_string())),
+            Ok(_) | Err(Errno::EINTR) => {}
+            Err(err) => return Err(Error::Failed(format!("Error polling device: {}", err))),
+        }
+        match events.read().map_err(Error::Failed)? {
+            ReadRes::Packet(event) => {
+                if let Some(value) = f(event) {
+                    return Ok(value);
+                }
+            }
+            ReadRes::Continue => {}
+            ReadRes::EOF => return Err(Error::Failed("Device disconnected".to_string())),
+        }
+    }
+}
+
+// Run a command on one device, returning properties to output
+fn run(
+    command: &Command,
+    device: &mut Device,
+    configs: &mut HashMap<String, MouseConfig>,
+) -> Result<Map<String, Value>, Error> {
+    let mut output = Map::new();
+    output.insert("serial".to_string(), device.serial.clone().into());
+
+    let config = configs
+        .entry(device.serial.clone())
+        .or_insert_with(|| MouseConfig::new(device.device.clone()));
+
+    match command {
+        Command::List => {
+            let (major, minor, patch) = device.version;
+            output.insert("model".to_string(), device.device.clone().into());
+            output.insert(
+                "firmware".to_string(),
+                format!("{}.{}.{}", major, minor, patch).into(),
+            );
+        }
+        Command::Info => {
+            let (major, minor, patch) = device.version;
+            let battery = device.battery()?;
+            let (dpi, left_handed) = device.dpi_and_left_handed()?;
+            let bindings = device.bindings()?;
+            output.insert("model".to_string(), device.device.clone().into());
+            output.insert(
+                "firmware".to_string(),
+                format!("{}.{}.{}", major, minor, patch).into(),
+            );
+            output.insert("battery".to_string(), battery.into());
+            output.insert("dpi".to_string(), dpi.into());
+            output.insert("left-handed".to_string(), left_handed.into());
+            output.insert("profile".to_string(), config.profile_num().into());
+            output.insert("bindings".to_string(), bindings.into());
+        }
+        Command::Battery => {
+            output.insert("battery".to_string(), device.battery()?.into());
+        }
+        Command::GetDpi => {
+            let (dpi, _) = device.dpi_and_left_handed()?;
+            output.insert("dpi".to_string(), dpi.into());
+        }
+        Command::SetDpi(dpi) => {
+            policy().check_dpi((*dpi).into()).map_err(Error::Failed)?;
+            config.profile_mut().dpi = (*dpi).into();
+            let dpi = round_dpi(config.profile().dpi);
+            device.mouse.set_dpi(dpi)?;
+            output.insert("dpi".to_string(), dpi.into());
+        }
+        Command::Bind(button, binding) => {
+            policy()
+                .check_binding(*button, binding)
+                .map_err(Error::Failed)?;
+            let profile = config.profile_mut();
+            if *binding == Binding::Preset(button.def_binding().id) {
+                profile.bindings.remove(button);
+            } else {
+                profile.bindings.insert(*button, binding.clone());
+            }
+            device.mouse.set_button(profile.button(*button), false)?;
+            output.insert(button.name().to_string(), binding.label().into());
+        }
+        Command::LeftHanded(None) => {
+            let (_, left_handed) = device.dpi_and_left_handed()?;
+            output.insert("left-handed".to_string(), left_handed.into());
+        }
+        Command::LeftHanded(Some(left_handed)) => {
+            policy()
+                .check_left_handed(*left_handed)
+                .map_err(Error::Failed)?;
+            config.profile_mut().left_handed = *left_handed;
+            device.mouse.set_left_handed(*left_handed)?;
+            output.insert("left-handed".to_string(), (*left_handed).into());
+        }
+        Command::Export(path) => {
+            config.export(path).map_err(|err| {
+                Error::Failed(format!("Failed to export to `{}`: {}", path.display(), err))
+            })?;
+        }
+        Command::Import(path) => {
+            let imported = MouseConfig::import(path)
+                .and_then(|imported| {
+                    config.check_model(&imported)?;
+                    Ok(imported)
+                })
+                .map_err(|err| {
+                    Error::Failed(format!("Failed to import `{}`: {}", path.display(), err))
+                })?;
+            *config = imported;
+            device.apply_config(config)?;
+        }
+        Command::ApplyProfile(profile) => {
+            if *profile >= config.profiles().len() {
+                return Err(Error::Usage(format!("Invalid profile {}", profile)));
+            }
+            config.select_profile(*profile);
+            device.apply_profile(config.profile())?;
+            output.insert("profile".to_string(), (*profile).into());
+        }
+        Command::Reset => {
+            device.mouse.reset()?;
+            config.reset_profiles();
+            config.select_profile(0);
+            device.apply_config(config)?;
+        }
+        Command::Daemon => unreachable!(),
+    }
+
+    Ok(output)
+}
+
+fn print_text(output: &Map<String, Value>, indent: usize) {
+    for (key, value) in output {
+        match value {
+            Value::Object(map) => {
+                println!("{:indent$}{}:", "", key, indent = indent);
+                print_text(map, indent + 2);
+            }
+            Value::String(s) => println!("{:indent$}{}: {}", "", key, s, indent = indent),
+            _ => println!("{:indent$}{}: {}", "", key, value, indent = indent),
+        }
+    }
+}
+
+fn main_inner(json_output: &mut bool) -> Result<(), Error> {
+    let mut serial = None;
+    let mut command_args = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            "--json" => *json_output = true,
+            "-d" | "--device" => {
+                serial = Some(
+                    args.next()
+                        .ok_or_else(|| Error::Usage(format!("Missing argument to `{}`", arg)))?,
+                );
+            }
+            _ if arg.starts_with('-') => {
+                return Err(Error::Usage(format!("Unknown option `{}`", arg)))
+            }
+            _ => command_args.push(arg),
+        }
+    }
+    let command = Command::parse(&command_args)?;
+
+    if let Command::Daemon = command {
+        return daemon::run();
+    }
+
+    let (mut devices, failed) = open_devices(serial.as_deref())?;
+    let mut failed_outputs = Vec::new();
+    let mut res = Ok(());
+    if let Command::List = command {
+        // List devices that failed, and continue with the rest
+        for (path, err) in failed {
+            let mut output = Map::new();
+            output.insert("path".to_string(), path.display().to_string().into());
+            output.insert("error".to_string(), err.message().into());
+            failed_outputs.push(output);
+            res = res.and(Err(err));
+        }
+    } else if devices.is_empty() || serial.is_none() {
+        // Fail if the device that failed may be the one selected
+        if let Some((_, err)) = failed.into_iter().next() {
+            return Err(err);
+        }
+    }
+    if devices.is_empty() && failed_outputs.is_empty() {
+        return Err(Error::NoDevice);
+    }
+    if command.single_device() && devices.len() > 1 {
+        return Err(Error::Usage(
+            "Multiple devices found; select one with `--device`".to_string(),
+        ));
+    }
+
+    let (mut configs, errors) = load_config();
+    if let Some(err) = errors.first() {
+        return Err(Error::Failed(err.to_string()));
+    }
+    let mut outputs = Vec::new();
+    for device in &mut devices {
+        match run(&command, device, &mut configs) {
+            Ok(output) => outputs.push(output),
+            Err(err) => {
+                res = Err(err);
+                break;
+            }
+        }
+    }
+    outputs.extend(failed_outputs);
+    if command.modifies_config() {
+        let configs = devices
+            .iter()
+            .filter_map(|device| Some((&device.serial, configs.get(&device.serial)?)));
+        if let Err(err) = save_config(configs) {
+            res = res.and(Err(Error::Failed(err)));
+        }
+    }
+
+    if *json_output {
+        println!("{}", json!(outputs));
+    } else {
+        for output in &mut outputs {
+            // Serial, or path of a device that failed to open
+            if let Some(Value::String(name)) =
+                output.remove("serial").or_else(|| output.remove("path"))
+            {
+                println!("{}", name);
+            }
+            print_text(output, 2);
+        }
+    }
+
+    res
+}
+
+fn main() {
+    let mut json_output = false;
+    if let Err(err) = main_inner(&mut json_output) {
+        if json_output {
+            eprintln!("{}", json!({ "error": err.message() }));
+        } else {
+            eprintln!("{}", err);
+        }
+        process::exit(err.exit_code());
+    }
+}
@@ -0,0 +1,1029 @@
+//! Saved configuration for each mouse: profiles of button bindings, and
+//! sensitivity.
+
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    env, fmt,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    bindings::{Entry, HardwareButton, PresetBinding},
+    button::{decode_action, describe_action},
+    keycode::KeyCombination,
+    shortcut::Shortcut,
+    Button, Op, PressType,
+};
+
+pub const DPI_STEP: f64 = 50.;
+// XXX Default DPI should depend on device model
+pub const DEFAULT_DPI: f64 = 1200.;
+
+/// Round DPI to a value supported by the device
+pub fn round_dpi(dpi: f64) -> u16 {
+    ((dpi / DPI_STEP).round() * DPI_STEP) as u16
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum Binding {
+    Preset(PresetBinding),
+    Shortcut(Shortcut),
+    // Binding read from device, that isn't recognized. Kept as encoded bytes,
+    // since it may not be possible to decode.
+    Custom { action: Vec<u8> },
+}
+
+impl Binding {
+    pub fn for_action(action: &[Op]) -> Option<Self> {
+        if let Some(entry) = Entry::for_binding(action) {
+            Some(Binding::Preset(entry.id))
+        } else {
+            Shortcut::from_binding(action).map(Binding::Shortcut)
+        }
+    }
+
+    pub fn for_button(button: &Button) -> Self {
+        match button.decode_action() {
+            Ok(action) => {
+                if let Some(binding) = Self::for_action(&action) {
+                    return binding;
+                }
+                eprintln!("Unrecognized action: {:?}", action);
+            }
+            Err(err) => {
+                eprintln!("Unable to decode button action: {}", err);
+            }
+        }
+        Binding::Custom {
+            action: button.action().to_vec(),
+        }
+    }
+
+    /// Parse a preset name, like `volume-up`, or a key combination, like
+    /// `Ctrl+Shift+T`
+    pub fn parse(s: &str) -> Result<Self, String> {
+        if let Ok(preset) = serde_json::from_value(serde_json::Value::String(s.to_string())) {
+            return Ok(Binding::Preset(preset));
+        }
+        let combination = s.parse::<KeyCombination>()?;
+        let shortcut = Shortcut::from_combination(&combination)
+            .ok_or_else(|| format!("Shortcut must have exactly one key: `{}`", s))?;
+        // Use preset, if shortcut matches one
+        Ok(Self::for_action(&shortcut.binding()).unwrap_or(Binding::Shortcut(shortcut)))
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Binding::Preset(binding) => binding.entry().label.to_string(),
+            Binding::Shortcut(shortcut) => shortcut.label(),
+            Binding::Custom { action } => match decode_action(action) {
+                Ok(ops) => describe_action(&ops),
+                Err(_) => "Unknown".to_string(),
+            },
+        }
+    }
+
+    pub fn button(&self, id: HardwareButton) -> Button {
+        let (id, host_id, press_type) = (id as u8, 0, PressType::Normal); // XXX
+        match self {
+            Binding::Preset(binding) => {
+                Button::new(id, host_id, press_type, &binding.entry().binding)
+            }
+            Binding::Shortcut(shortcut) => {
+                Button::new(id, host_id, press_type, &shortcut.binding())
+            }
+            Binding::Custom { action } => Button::new_raw(id, host_id, press_type, action.clone()),
+        }
+    }
+
+    pub fn is_custom(&self) -> bool {
+        matches!(self, Binding::Custom { .. })
+    }
+}
+
+fn default_dpi() -> f64 {
+    DEFAULT_DPI
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct Profile {
+    pub name: Option<String>,
+    pub bindings: HashMap<HardwareButton, Binding>,
+    pub left_handed: bool,
+    #[serde(default = "default_dpi")]
+    pub dpi: f64,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            name: None,
+            bindings: HashMap::new(),
+            left_handed: false,
+            dpi: DEFAULT_DPI,
+        }
+    }
+}
+
+impl Profile {
+    /// Button to write to the device for `id`
+    pub fn button(&self, id: HardwareButton) -> Button {
+        match self.bindings.get(&id) {
+            Some(binding) => binding.button(id),
+            None => Button::new(id as u8, 0, PressType::Normal, &[]), // XXX
+        }
+    }
+
+    fn binding_label(&self, id: HardwareButton) -> String {
+        match self.bindings.get(&id) {
+            Some(binding) => binding.label(),
+            None => id.def_binding().label.to_string(),
+        }
+    }
+
+    /// Describe each setting that differs in `other`, for previewing changes
+    /// before replacing this profile with it
+    pub fn changes(&self, other: &Profile) -> Vec<String> {
+        let mut changes = Vec::new();
+        if self.name != other.name {
+            let name = |x: &Option<String>| x.clone().unwrap_or_else(|| "Unnamed".to_string());
+            changes.push(format!(
+                "Name: {} → {}",
+                name(&self.name),
+                name(&other.name)
+            ));
+        }
+        if round_dpi(self.dpi) != round_dpi(other.dpi) {
+            changes.push(format!(
+                "DPI: {} → {}",
+                round_dpi(self.dpi),
+                round_dpi(other.dpi)
+            ));
+        }
+        if self.left_handed != other.left_handed {
+            let hand = |x| if x { "Left" } else { "Right" };
+            changes.push(format!(
+                "Handedness: {} → {}",
+                hand(self.left_handed),
+                hand(other.left_handed)
+            ));
+        }
+        for id in HardwareButton::iter() {
+            let (old, new) = (self.binding_label(id), other.binding_label(id));
+            if old != new {
+                changes.push(format!("Button {}: {} → {}", id.name(), old, new));
+            }
+        }
+        changes
+    }
+}
+
+// Selects a profile while a matching application has focus
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AppRule {
+    // App-id on Wayland, or `WM_CLASS` instance or class on X11. Not case sensitive.
+    pub app_id: String,
+    pub profile: usize,
+}
+
+impl AppRule {
+    pub fn matches(&self, app_ids: &[String]) -> bool {
+        app_ids.iter().any(|x| x.eq_ignore_ascii_case(&self.app_id))
+    }
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct MouseConfig {
+    // Must always be non-empty
+    profiles: Vec<Profile>,
+    // Must Always be in range
+    profile_num: usize,
+    // DPI shared by all profiles, in configs from before DPI was per profile
+    #[serde(default, rename = "dpi", skip_serializing)]
+    legacy_dpi: Option<f64>,
+    pub device: String,
+    #[serde(default)]
+    pub rules: Vec<AppRule>,
+    // Profile selected by a rule, overriding `profile_num`
+    #[serde(skip)]
+    rule_profile: Option<usize>,
+}
+
+impl MouseConfig {
+    pub fn new(device: String) -> Self {
+        Self {
+            profiles: vec![Profile::default()],
+            profile_num: 0,
+            legacy_dpi: None,
+            device,
+            rules: Vec::new(),
+            rule_profile: None,
+        }
+    }
+
+    // Update a config loaded from an older version, and ensure invariants
+    // hold for a config that was edited by hand
+    fn migrate(&mut self) {
+        if let Some(dpi) = self.legacy_dpi.take() {
+            for profile in &mut self.profiles {
+                profile.dpi = dpi;
+            }
+        }
+        if self.profiles.is_empty() {
+            self.profiles.push(Profile::default());
+        }
+        if self.profile_num >= self.profiles.len() {
+            self.profile_num = 0;
+        }
+    }
+
+    pub fn profile(&self) -> &Profile {
+        &self.profiles[self.profile_num()]
+    }
+
+    pub fn profile_mut(&mut self) -> &mut Profile {
+        let profile_num = self.profile_num();
+        &mut self.profiles[profile_num]
+    }
+
+    pub fn profiles(&self) -> &[Profile] {
+        &self.profiles
+    }
+
+    pub fn profiles_mut(&mut self) -> &mut [Profile] {
+        &mut self.profiles
+    }
+
+    pub fn select_profile(&mut self, profile: usize) {
+        if profile < self.profiles.len() {
+            self.profile_num = profile;
+            self.rule_profile = None;
+        }
+    }
+
+    pub fn profile_labels(&self) -> Vec<String> {
+        self.profiles
+            .iter()
+            .enumerate()
+            .map(|(n, profile)| match &profile.name {
+                Some(name) => name.clone(),
+                None => format!("Configuration {}", n + 1),
+            })
+            .collect()
+    }
+
+    // Update profile indices after profiles are removed or moved. `f` maps
+    // an old index to the new one, or `None` if the profile was removed.
+    fn remap_profiles<F: Fn(usize) -> Option<usize>>(&mut self, f: F) {
+        self.profile_num =
+            f(self.profile_num).unwrap_or_else(|| self.profile_num.min(self.profiles.len() - 1));
+        self.rule_profile = self.rule_profile.and_then(&f);
+        self.rules = self
+            .rules
+            .drain(..)
+            .filter_map(|rule| {
+                Some(AppRule {
+                    profile: f(rule.profile)?,
+                    ..rule
+                })
+            })
+            .collect();
+    }
+
+    /// Replace all profiles with one default profile
+    pub fn reset_profiles(&mut self) {
+        self.profiles = vec![Profile::default()];
+        self.remap_profiles(|i| if i == 0 { Some(0) } else { None });
+    }
+
+    /// Add a default profile, returning its index
+    pub fn add_profile(&mut self) -> usize {
+        self.profiles.push(Profile::default());
+        self.profiles.len() - 1
+    }
+
+    /// Add a copy of profile `idx` after it, returning the copy's index
+    pub fn duplicate_profile(&mut self, idx: usize) -> Option<usize> {
+        let mut profile = self.profiles.get(idx)?.clone();
+        profile.name = Some(format!("{} (Copy)", self.profile_labels()[idx]));
+        self.profiles.insert(idx + 1, profile);
+        self.remap_profiles(|i| Some(if i > idx { i + 1 } else { i }));
+        Some(idx + 1)
+    }
+
+    /// Remove profile `idx`, and rules that select it. The last profile
+    /// can't be removed.
+    pub fn remove_profile(&mut self, idx: usize) -> bool {
+        if self.profiles.len() <= 1 || idx >= self.profiles.len() {
+            return false;
+        }
+        self.profiles.remove(idx);
+        self.remap_profiles(|i| match i.cmp(&idx) {
+            Ordering::Less => Some(i),
+            Ordering::Equal => None,
+            Ordering::Greater => Some(i - 1),
+        });
+        true
+    }
+
+    /// Move profile `from` to index `to`
+    pub fn move_profile(&mut self, from: usize, to: usize) -> bool {
+        if from >= self.profiles.len() || to >= self.profiles.len() {
+            return false;
+        }
+        let profile = self.profiles.remove(from);
+        self.profiles.insert(to, profile);
+        self.remap_profiles(|i| {
+            Some(if i == from {
+                to
+            } else if from < i && i <= to {
+                i - 1
+            } else if to <= i && i < from {
+                i + 1
+            } else {
+                i
+            })
+        });
+        true
+    }
+
+    /// Active profile, which may be selected by a rule
+    pub fn profile_num(&self) -> usize {
+        self.rule_profile.unwrap_or(self.profile_num)
+    }
+
+    /// Select the profile for the first rule matching the focused application,
+    /// or the manually selected profile if none match. Returns `true` if the
+    /// active profile changed.
+    pub fn apply_rules(&mut self, app_ids: &[String]) -> bool {
+        let old = self.profile_num();
+        self.rule_profile = self
+            .rules
+            .iter()
+            .find(|rule| rule.profile < self.profiles.len() && rule.matches(app_ids))
+            .map(|rule| rule.profile);
+        self.profile_num() != old
+    }
+
+    /// Check that an imported config is for the same model of mouse
+    pub fn check_model(&self, imported: &MouseConfig) -> Result<(), String> {
+        if imported.device == self.device {
+            Ok(())
+        } else {
+            Err(format!(
+                "Configuration is for `{}`, but this mouse is `{}`",
+                imported.device, self.device
+            ))
+        }
+    }
+
+    /// Index of a profile with the same name and settings as `profile`
+    pub fn find_profile(&self, profile: &Profile) -> Option<usize> {
+        self.profiles
+            .iter()
+            .position(|x| x.changes(profile).is_empty())
+    }
+
+    /// Replace the profile at `slot` with `profile`, or add it if `slot` is
+    /// `None`. Returns the index of the profile.
+    pub fn import_profile(&mut self, profile: Profile, slot: Option<usize>) -> usize {
+        match slot.filter(|x| *x < self.profiles.len()) {
+            Some(slot) => {
+                self.profiles[slot] = profile;
+                slot
+            }
+            None => {
+                self.profiles.push(profile);
+                self.profiles.len() - 1
+            }
+        }
+    }
+
+    pub fn import(path: &Path) -> Result<Self, String> {
+        let file = File::open(path).map_err(|x| x.to_string())?;
+        let mut config: Self = serde_json::from_reader(file).map_err(|x| x.to_string())?;
+        config.migrate();
+        Ok(config)
+    }
+
+    pub fn export(&self, path: &Path) -> Result<(), String> {
+        let file = File::create(path).map_err(|x| x.to_string())?;
+        serde_json::to_writer_pretty(file, self).map_err(|x| x.to_string())
+    }
+}
+
+fn data_dir() -> PathBuf {
+    if let Ok(dir) = env::var("XDG_DATA_HOME") {
+        dir.into()
+    } else if let Ok(dir) = env::var("HOME") {
+        let mut path = PathBuf::from(dir);
+        path.push(".local/share");
+        path
+    } else {
+        panic!("`XDG_DATA_HOME` and `HOME` undefined")
+    }
+}
+
+pub(crate) fn app_data_dir() -> PathBuf {
+    let mut dir = data_dir();
+    dir.push("org.pop_os.mouseconfigurator");
+    if let Err(err) = std::fs::create_dir_all(&dir) {
+        panic!("Failed to create directory `{}`: {}", dir.display(), err);
+    }
+    dir
+}
+
+/// Version of the config format, incremented when a change requires a
+/// migration
+pub const CONFIG_VERSION: u64 = 3;
+// Last version of `config.json`, which held every device before each had its
+// own file
+const LEGACY_VERSION: u64 = 2;
+
+type Migration = fn(&mut serde_json::Value) -> Result<(), String>;
+
+// `MIGRATIONS[n]` updates `config.json` from version `n + 1` to `n + 2`
+const MIGRATIONS: &[Migration] = &[migrate_v1];
+
+// Version 1 was a bare map of serial numbers to configs
+fn migrate_v1(value: &mut serde_json::Value) -> Result<(), String> {
+    if !value.is_object() {
+        return Err("expected a map of devices".to_string());
+    }
+    *value = serde_json::json!({ "devices": value.take() });
+    Ok(())
+}
+
+/// Failure to load a saved configuration
+#[derive(Debug)]
+pub struct ConfigError {
+    /// Device the config is for, or `None` if it affects every device
+    pub serial: Option<String>,
+    pub message: String,
+    /// Set if saving would overwrite a config that couldn't be read, and
+    /// wasn't backed up
+    pub read_only: bool,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+enum ParseError {
+    Invalid(String),
+    // Saved by a newer version, with a format this can't read
+    Newer(u64),
+}
+
+impl From<serde_json::Error> for ParseError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Invalid(err.to_string())
+    }
+}
+
+// Versions before 2 weren't recorded
+fn parse_version(value: &serde_json::Value) -> Result<u64, ParseError> {
+    match value.get("version") {
+        Some(version) => version
+            .as_u64()
+            .filter(|x| *x > 0)
+            .ok_or_else(|| ParseError::Invalid("invalid version".to_string())),
+        None => Ok(1),
+    }
+}
+
+fn parse_legacy_config(data: &str) -> Result<HashMap<String, MouseConfig>, ParseError> {
+    let mut value: serde_json::Value = serde_json::from_str(data)?;
+    let version = parse_version(&value)?;
+    if version > CONFIG_VERSION {
+        return Err(ParseError::Newer(version));
+    } else if version > LEGACY_VERSION {
+        return Err(ParseError::Invalid(format!(
+            "unexpected format version {}",
+            version
+        )));
+    }
+    for migration in &MIGRATIONS[version as usize - 1..] {
+        migration(&mut value).map_err(ParseError::Invalid)?;
+    }
+
+    let devices = value
+        .get_mut("devices")
+        .map(serde_json::Value::take)
+        .unwrap_or_default();
+    let mut config: HashMap<String, MouseConfig> = serde_json::from_value(devices)?;
+    for mouse in config.values_mut() {
+        mouse.migrate();
+    }
+    Ok(config)
+}
+
+// Files without a version, like an exported config copied into the
+// directory, are read the same as the current version
+fn parse_device_config(data: &str) -> Result<MouseConfig, ParseError> {
+    let value: serde_json::Value = serde_json::from_str(data)?;
+    let version = parse_version(&value)?;
+    if version > CONFIG_VERSION {
+        return Err(ParseError::Newer(version));
+    }
+    let mut config: MouseConfig = serde_json::from_value(value)?;
+    config.migrate();
+    Ok(config)
+}
+
+/// Contents of the config file for a device
+pub fn serialize_config(config: &MouseConfig) -> String {
+    let mut value = serde_json::to_value(config).unwrap();
+    value["version"] = CONFIG_VERSION.into();
+    // Pretty printed, since it may be edited by hand
+    serde_json::to_string_pretty(&value).unwrap()
+}
+
+fn legacy_config_path() -> PathBuf {
+    let mut path = app_data_dir();
+    path.push("config.json");
+    path
+}
+
+/// Directory with the config file for each device
+pub fn devices_config_dir() -> PathBuf {
+    let mut path = app_data_dir();
+    path.push("devices");
+    path
+}
+
+// Characters percent-encoded in file names. Serial numbers come from the
+// device, so don't allow path separators.
+const ENCODED_CHARS: &[(char, &str)] = &[('%', "%25"), ('/', "%2F"), ('\0', "%00")];
+
+/// File name for a serial number, without extension
+pub(crate) fn encode_serial(serial: &str) -> String {
+    let mut encoded = String::with_capacity(serial.len());
+    for c in serial.chars() {
+        match ENCODED_CHARS.iter().find(|(x, _)| *x == c) {
+            Some((_, escape)) => encoded.push_str(escape),
+            None => encoded.push(c),
+        }
+    }
+    encoded
+}
+
+// Inverse of `encode_serial`. Other `%` sequences are kept as they are.
+fn decode_serial(name: &str) -> String {
+    let mut decoded = String::with_capacity(name.len());
+    let mut rest = name;
+    while let Some(idx) = rest.find('%') {
+        decoded.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+        match ENCODED_CHARS
+            .iter()
+            .find(|(_, escape)| rest.get(..escape.len()) == Some(escape))
+        {
+            Some((c, escape)) => {
+                decoded.push(*c);
+                rest = &rest[escape.len()..];
+            }
+            None => {
+                decoded.push('%');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn device_config_path(serial: &str) -> PathBuf {
+    let mut path = devices_config_dir();
+    path.push(format!("{}.json", encode_serial(serial)));
+    path
+}
+
+/// Serial number of the device a file in `devices_config_dir` is for
+pub fn serial_for_path(path: &Path) -> Option<String> {
+    if path.extension()? != "json" {
+        return None;
+    }
+    Some(decode_serial(path.file_stem()?.to_str()?))
+}
+
+// Move an unreadable config aside, so it isn't overwritten by the next save
+fn backup_config(path: &Path) -> io::Result<PathBuf> {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |x| x.as_secs());
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(format!(".{}.bak", time));
+    fs::rename(path, &backup)?;
+    Ok(backup.into())
+}
+
+fn parse_error(path: &Path, err: ParseError, backup: bool) -> (String, bool) {
+    let err = match err {
+        // Keep the file, so the newer version can still use it
+        ParseError::Newer(version) => {
+            return (
+                format!(
+                    "`{}` was saved by a newer version of Mouse Configurator (format version {}). Changes won't be saved.",
+                    path.display(),
+                    version
+                ),
+                true,
+            )
+        }
+        ParseError::Invalid(err) => err,
+    };
+    if !backup {
+        return (
+            format!(
+                "Failed to load `{}`: {}. Changes won't be saved until it is fixed.",
+                path.display(),
+                err
+            ),
+            true,
+        );
+    }
+    match backup_config(path) {
+        Ok(backup) => (
+            format!(
+                "Failed to load `{}`: {}. The file was moved to `{}`.",
+                path.display(),
+                err,
+                backup.display()
+            ),
+            false,
+        ),
+        Err(backup_err) => (
+            format!(
+                "Failed to load `{}`: {}. Changes won't be saved, since it couldn't be backed up: {}",
+                path.display(),
+                err,
+                backup_err
+            ),
+            true,
+        ),
+    }
+}
+
+fn load_device_file(
+    path: &Path,
+    serial: &str,
+    backup: bool,
+) -> Result<Option<MouseConfig>, ConfigError> {
+    let res = match fs::read_to_string(path) {
+        Ok(data) => parse_device_config(&data),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => {
+            return Err(ConfigError {
+                serial: Some(serial.to_string()),
+                message: format!("Failed to read `{}`: {}", path.display(), err),
+                read_only: true,
+            })
+        }
+    };
+    res.map(Some).map_err(|err| {
+        let (message, read_only) = parse_error(path, err, backup);
+        ConfigError {
+            serial: Some(serial.to_string()),
+            message,
+            read_only,
+        }
+    })
+}
+
+/// Load the config file for `serial`, or `None` if there isn't one. Unlike
+/// `load_config`, an unreadable file is left in place, since it may be in the
+/// middle of being edited.
+pub fn load_device_config(serial: &str) -> Result<Option<MouseConfig>, ConfigError> {
+    load_device_file(&device_config_path(serial), serial, false)
+}
+
+// Split `config.json` into a file for each device, without replacing
+// existing files
+fn migrate_legacy_config() -> Result<(), ConfigError> {
+    let path = legacy_config_path();
+    let data = match fs::read_to_string(&path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => {
+            return Err(ConfigError {
+                serial: None,
+                message: format!("Failed to read `{}`: {}", path.display(), err),
+                read_only: true,
+            })
+        }
+    };
+    let configs = parse_legacy_config(&data).map_err(|err| {
+        let (message, read_only) = parse_error(&path, err, true);
+        ConfigError {
+            serial: None,
+            message,
+            read_only,
+        }
+    })?;
+
+    let res = configs
+        .iter()
+        .filter(|(serial, _)| !device_config_path(serial).exists())
+        .try_for_each(|(serial, config)| save_device_config(serial, config))
+        .and_then(|()| fs::remove_file(&path).map_err(|x| x.to_string()));
+    res.map_err(|err| ConfigError {
+        serial: None,
+        message: format!("Failed to migrate `{}`: {}", path.display(), err),
+        // Saving could overwrite a config that hasn't been migrated yet
+        read_only: true,
+    })
+}
+
+/// Load the saved configuration for each device, by serial number.
+///
+/// Files that can't be parsed are backed up, and returned as errors. If
+/// `read_only` is set on an error, the config it is for must not be saved.
+pub fn load_config() -> (HashMap<String, MouseConfig>, Vec<ConfigError>) {
+    let mut configs = HashMap::new();
+    let mut errors = Vec::new();
+    if let Err(err) = migrate_legacy_config() {
+        errors.push(err);
+    }
+
+    let dir = devices_config_dir();
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return (configs, errors),
+        Err(err) => {
+            errors.push(ConfigError {
+                serial: None,
+                message: format!("Failed to read `{}`: {}", dir.display(), err),
+                read_only: true,
+            });
+            return (configs, errors);
+        }
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let serial = match serial_for_path(&path) {
+            Some(serial) => serial,
+            None => continue,
+        };
+        match load_device_file(&path, &serial, true) {
+            Ok(Some(config)) => {
+                configs.insert(serial, config);
+            }
+            Ok(None) => {}
+            Err(err) => errors.push(err),
+        }
+    }
+    (configs, errors)
+}
+
+// Write to a temporary file and rename it, so the config is never partly
+// written
+pub(crate) fn write_atomic(path: &Path, data: &str) -> io::Result<()> {
+    fs::create_dir_all(path.parent().unwrap())?;
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let res = (|| {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(data.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    })();
+    if res.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    res
+}
+
+/// Save the config for a device, replacing its file atomically
+pub fn save_device_config(serial: &str, config: &MouseConfig) -> Result<(), String> {
+    write_config_data(serial, &serialize_config(config))
+}
+
+/// Save the config for each device
+pub fn save_config<'a, T: Iterator<Item = (&'a String, &'a MouseConfig)>>(
+    config: T,
+) -> Result<(), String> {
+    for (serial, config) in config {
+        save_device_config(serial, config)?;
+    }
+    Ok(())
+}
+
+/// Save the output of `serialize_config` as the config for a device
+pub fn write_config_data(serial: &str, data: &str) -> Result<(), String> {
+    let path = device_config_path(serial);
+    write_atomic(&path, data).map_err(|err| format!("Failed to save `{}`: {}", path.display(), err))
+}
+
+/// Remove the saved config for a device
+pub fn remove_device_config(serial: &str) -> Result<(), String> {
+    let path = device_config_path(serial);
+    match fs::remove_file(&path) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(format!("Failed to remove `{}`: {}", path.display(), err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_select_profile() {
+        let mut config = MouseConfig::new("Brain".to_string());
+        for _ in 0..3 {
+            config.add_profile();
+        }
+        config.select_profile(1);
+        config.rules.push(AppRule {
+            app_id: "Krita".to_string(),
+            profile: 2,
+        });
+        config.rules.push(AppRule {
+            app_id: "firefox".to_string(),
+            profile: 7,
+        });
+
+        assert!(config.apply_rules(&["krita".to_string()]));
+        assert_eq!(config.profile_num(), 2);
+        assert!(!config.apply_rules(&["krita".to_string()]));
+        // Out of range rules are ignored
+        assert!(config.apply_rules(&["firefox".to_string()]));
+        assert_eq!(config.profile_num(), 1);
+
+        config.apply_rules(&["krita".to_string()]);
+        config.select_profile(3);
+        assert_eq!(config.profile_num(), 3);
+        assert!(!config.apply_rules(&[]));
+    }
+
+    #[test]
+    fn parse_binding() {
+        assert!(Binding::parse("volume-up").unwrap() == Binding::Preset(PresetBinding::VolumeUp));
+        assert!(Binding::parse("Ctrl+C").unwrap() == Binding::Preset(PresetBinding::Copy));
+        assert_eq!(
+            Binding::parse("Ctrl+Shift+T").unwrap().label(),
+            "Ctrl+Shift+T"
+        );
+        assert!(Binding::parse("Ctrl+A+B").is_err());
+    }
+
+    #[test]
+    fn custom_binding_round_trip() {
+        let button = Button::new(1, 0, PressType::Normal, &[Op::pause(100), Op::Kill]);
+        let binding = Binding::for_button(&button);
+        assert!(binding.is_custom());
+        assert_eq!(binding.label(), "Pause 100");
+
+        let json = serde_json::to_string(&binding).unwrap();
+        assert!(binding == serde_json::from_str(&json).unwrap());
+        assert_eq!(
+            binding.button(HardwareButton::Middle).action(),
+            button.action()
+        );
+    }
+
+    #[test]
+    fn migrate_dpi() {
+        let json = r#"{
+            "profiles": [{"name": null, "bindings": {}, "left_handed": false}],
+            "profile_num": 0,
+            "dpi": 800.0,
+            "device": "Brain"
+        }"#;
+        let mut config: MouseConfig = serde_json::from_str(json).unwrap();
+        config.migrate();
+        assert_eq!(config.profile().dpi, 800.);
+
+        let json = serde_json::to_string(&config).unwrap();
+        let config: MouseConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(config.legacy_dpi, None);
+        assert_eq!(config.profile().dpi, 800.);
+    }
+
+    #[test]
+    fn config_versions() {
+        let config = MouseConfig::new("Brain".to_string());
+        let v1 = serde_json::json!({ "ABC123": config }).to_string();
+        let configs = parse_legacy_config(&v1).ok().unwrap();
+        assert_eq!(configs["ABC123"].device, "Brain");
+
+        let v2 = serde_json::json!({ "version": 2, "devices": { "ABC123": config } }).to_string();
+        assert!(parse_legacy_config(&v2).is_ok());
+        assert!(matches!(
+            parse_legacy_config(r#"["ABC123"]"#),
+            Err(ParseError::Invalid(_))
+        ));
+
+        let data = serialize_config(&config);
+        assert_eq!(parse_device_config(&data).ok().unwrap().device, "Brain");
+        assert!(matches!(
+            parse_device_config(r#"{"version": 99}"#),
+            Err(ParseError::Newer(99))
+        ));
+        assert_eq!(
+            serial_for_path(Path::new("/data/devices/ABC123.json")).as_deref(),
+            Some("ABC123")
+        );
+        assert_eq!(serial_for_path(Path::new("ABC123.json.tmp")), None);
+    }
+
+    #[test]
+    fn serial_path_round_trip() {
+        for serial in ["ABC123", "a/b", "a_b", "a\0b", "100%", "%2F", "../x"] {
+            let path = device_config_path(serial);
+            assert_eq!(path.parent(), Some(devices_config_dir().as_path()));
+            assert_eq!(serial_for_path(&path).as_deref(), Some(serial));
+        }
+        assert_ne!(device_config_path("a/b"), device_config_path("a_b"));
+        // Files from before serials were encoded
+        assert_eq!(
+            serial_for_path(Path::new("50%off.json")).as_deref(),
+            Some("50%off")
+        );
+    }
+
+    #[test]
+    fn import_profile() {
+        let mut config = MouseConfig::new("Brain".to_string());
+        assert!(config
+            .check_model(&MouseConfig::new("Other".to_string()))
+            .is_err());
+
+        let mut profile = Profile {
+            name: Some("Drawing".to_string()),
+            dpi: 800.,
+            ..Profile::default()
+        };
+        profile.bindings.insert(
+            HardwareButton::LeftTop,
+            Binding::Preset(PresetBinding::Copy),
+        );
+        let changes = config.profile().changes(&profile);
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[1], "DPI: 1200 → 800");
+
+        assert_eq!(config.find_profile(&profile), None);
+        assert_eq!(config.import_profile(profile.clone(), None), 1);
+        assert_eq!(config.find_profile(&profile), Some(1));
+        assert_eq!(config.import_profile(profile, Some(0)), 0);
+        assert_eq!(config.profiles().len(), 2);
+        assert!(config.profile().changes(&config.profiles()[1]).is_empty());
+    }
+
+    #[test]
+    fn edit_profiles() {
+        let mut config = MouseConfig::new("Brain".to_string());
+        assert_eq!(config.add_profile(), 1);
+        assert_eq!(config.add_profile(), 2);
+        config.profiles_mut()[1].name = Some("Precision".to_string());
+        config.rules = vec![
+            AppRule {
+                app_id: "gimp".to_string(),
+                profile: 1,
+            },
+            AppRule {
+                app_id: "firefox".to_string(),
+                profile: 2,
+            },
+        ];
+        config.select_profile(2);
+
+        assert_eq!(config.duplicate_profile(1), Some(2));
+        assert_eq!(config.profile_labels()[2], "Precision (Copy)");
+        assert_eq!(config.profile_num(), 3);
+        assert_eq!(config.rules[1].profile, 3);
+
+        assert!(config.move_profile(3, 0));
+        assert_eq!(config.profile_num(), 0);
+        assert_eq!(config.rules[0].profile, 2);
+        assert_eq!(config.rules[1].profile, 0);
+
+        // Rules for a removed profile are removed
+        assert!(config.remove_profile(0));
+        assert_eq!(config.profile_num(), 0);
+        assert_eq!(config.rules.len(), 1);
+        assert_eq!(config.rules[0].profile, 1);
+
+        // Moving keeps both the manual and rule selection on their profiles
+        config.select_profile(0);
+        assert!(config.apply_rules(&[config.rules[0].app_id.clone()]));
+        assert!(config.move_profile(0, 1));
+        assert_eq!(config.profile_num(), 0);
+        assert!(config.apply_rules(&[]));
+        assert_eq!(config.profile_num(), 1);
+
+        while config.profiles().len() > 1 {
+            assert!(config.remove_profile(0));
+        }
+        assert!(!config.remove_profile(0));
+    }
+}
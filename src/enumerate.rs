@@ -21,6 +21,17 @@ impl DeviceInfo {
     }
 }
 
+/// Change to a device, from `monitor`
+#[derive(Debug)]
+pub enum MonitorEvent {
+    Added(DeviceInfo),
+    /// Device node removed. May not have been a matching device, since it
+    /// can't be checked after removal.
+    Removed(PathBuf),
+    /// Matching device changed, like a Bluetooth device being bound or unbound
+    Changed(DeviceInfo),
+}
+
 fn parse_hid_id(id: &str) -> Option<(u16, u16)> {
     let mut iter = id.split(':');
     let _ = iter.next()?;
@@ -76,9 +87,9 @@ struct PollMonitorIter {
 }
 
 impl Iterator for PollMonitorIter {
-    type Item = DeviceInfo;
+    type Item = MonitorEvent;
 
-    fn next(&mut self) -> Option<DeviceInfo> {
+    fn next(&mut self) -> Option<MonitorEvent> {
         loop {
             if self.poll {
                 let fd = PollFd::new(self.monitor.as_raw_fd(), PollFlags::POLLIN);
@@ -86,19 +97,27 @@ impl Iterator for PollMonitorIter {
             }
             self.poll = false;
             if let Some(evt) = self.monitor.next() {
-                if evt.event_type() == udev::EventType::Add {
-                    if let Some(device_info) = match_device(&evt.device()) {
-                        return Some(device_info);
+                let device = evt.device();
+                let event = match evt.event_type() {
+                    udev::EventType::Add => match_device(&device).map(MonitorEvent::Added),
+                    udev::EventType::Remove => device
+                        .devnode()
+                        .map(|devnode| MonitorEvent::Removed(devnode.to_owned())),
+                    udev::EventType::Change | udev::EventType::Bind | udev::EventType::Unbind => {
+                        match_device(&device).map(MonitorEvent::Changed)
                     }
+                    _ => None,
+                };
+                if let Some(event) = event {
+                    return Some(event);
                 }
-            } else {
             }
             self.poll = true;
         }
     }
 }
 
-pub fn monitor() -> io::Result<impl Iterator<Item = DeviceInfo> + 'static> {
+pub fn monitor() -> io::Result<impl Iterator<Item = MonitorEvent> + 'static> {
     let monitor = udev::MonitorBuilder::new()?
         .match_subsystem("hidraw")?
         .listen()?;
@@ -97,7 +97,7 @@ impl HpMouseEvents {
     }
 
     fn report_1_packet_1(&mut self, data: &[u8]) -> Option<Event> {
-        println!("Update {}", data.len());
+        eprintln!("Update {}", data.len());
 
         if data.len() <= 3 {
             // Buffer too small
@@ -256,7 +256,7 @@ impl HpMouseEvents {
         let header = Header::new(data).ok_or_else(|| "Invalid header".to_string())?;
 
         let kind_opt = header.kind();
-        println!(
+        eprintln!(
             " signature {:04X} {:?} length {} sequence {}",
             header.signature, kind_opt, header.length, header.sequence
         );
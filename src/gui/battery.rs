@@ -0,0 +1,100 @@
+//! Battery level icons, and desktop notifications when the battery is low.
+
+use gtk4::{gio, prelude::*};
+
+// Used if the device doesn't report thresholds
+const DEFAULT_LOW_LEVEL: u8 = 15;
+const DEFAULT_CRIT_LEVEL: u8 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Alert {
+    Low,
+    Critical,
+}
+
+// Thresholds are percentages, or `0xFF` if not set
+fn threshold(value: u8, default: u8) -> u8 {
+    if (1..=100).contains(&value) {
+        value
+    } else {
+        default
+    }
+}
+
+fn alert(level: u8, low_level: u8, crit_level: u8) -> Option<Alert> {
+    if level <= threshold(crit_level, DEFAULT_CRIT_LEVEL) {
+        Some(Alert::Critical)
+    } else if level <= threshold(low_level, DEFAULT_LOW_LEVEL) {
+        Some(Alert::Low)
+    } else {
+        None
+    }
+}
+
+/// Symbolic icon for a battery level, in steps of 10%
+pub fn icon_name(level: Option<u8>) -> String {
+    match level {
+        Some(level) => format!("battery-level-{}-symbolic", (level.min(100) + 5) / 10 * 10),
+        None => "battery-missing-symbolic".to_string(),
+    }
+}
+
+/// Notify if the level crossed the low or critical threshold since
+/// `previous`, and withdraw the notification once the mouse is charged.
+pub fn notify_level_change(
+    serial: &str,
+    previous: Option<u8>,
+    level: u8,
+    low_level: u8,
+    crit_level: u8,
+) {
+    let app = match gio::Application::default() {
+        Some(app) => app,
+        None => return,
+    };
+    let id = format!("battery-{}", serial);
+    let old_alert = alert(previous.unwrap_or(100), low_level, crit_level);
+    let new_alert = alert(level, low_level, crit_level);
+
+    if new_alert.is_none() {
+        if old_alert.is_some() {
+            app.withdraw_notification(&id);
+        }
+        return;
+    }
+    if new_alert <= old_alert {
+        return;
+    }
+
+    let (title, priority) = match new_alert {
+        Some(Alert::Critical) => (
+            "Mouse Battery Critically Low",
+            gio::NotificationPriority::Urgent,
+        ),
+        _ => ("Mouse Battery Low", gio::NotificationPriority::High),
+    };
+    let notification = gio::Notification::new(title);
+    notification.set_body(Some(&format!(
+        "{}% remaining on mouse {}. Charge it soon.",
+        level, serial
+    )));
+    notification.set_icon(&gio::ThemedIcon::new(&icon_name(Some(level))));
+    notification.set_priority(priority);
+    app.send_notification(Some(&id), &notification);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn battery_alert() {
+        assert_eq!(alert(50, 20, 10), None);
+        assert_eq!(alert(20, 20, 10), Some(Alert::Low));
+        assert_eq!(alert(10, 20, 10), Some(Alert::Critical));
+        // Unset thresholds use defaults
+        assert_eq!(alert(12, 0xFF, 0xFF), Some(Alert::Low));
+        assert_eq!(icon_name(Some(94)), "battery-level-90-symbolic");
+        assert_eq!(icon_name(Some(96)), "battery-level-100-symbolic");
+    }
+}
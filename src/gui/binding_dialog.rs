@@ -1,24 +1,91 @@
 use gtk4::{glib, pango, prelude::*};
+use once_cell::sync::Lazy;
 use relm4::{send, view, ComponentUpdate, Model, Sender, Widgets};
 use std::{cell::Cell, collections::HashMap, ptr, rc::Rc};
 
 use crate::{
-    bindings::{Category, Entry, HardwareButton, BINDINGS},
-    profile::Binding,
+    shortcut::{self, CapturedShortcut},
     util, AppMsg,
 };
+use mouse_configurator::{
+    bindings::{HardwareButton, BINDINGS},
+    config::Binding,
+    policy::policy,
+    presets::PRESETS,
+};
+
+pub struct Category {
+    label: &'static str,
+    entries: Vec<Entry>,
+}
+
+pub struct Entry {
+    label: &'static str,
+    keybind: Option<String>,
+    binding: Binding,
+}
+
+// Built-in presets, followed by presets installed as files
+static CATEGORIES: Lazy<Vec<Category>> = Lazy::new(|| {
+    let builtin = BINDINGS.iter().map(|category| Category {
+        label: category.label,
+        entries: category
+            .entries
+            .iter()
+            .map(|entry| Entry {
+                label: entry.label,
+                keybind: entry.keybind.map(str::to_string),
+                binding: Binding::Preset(entry.id),
+            })
+            .collect(),
+    });
+    let installed = PRESETS.iter().map(|category| Category {
+        label: &category.label,
+        entries: category
+            .presets
+            .iter()
+            .map(|preset| Entry {
+                label: &preset.label,
+                keybind: match &preset.binding {
+                    Binding::Preset(id) => id.entry().keybind.map(str::to_string),
+                    binding => Some(binding.label()),
+                },
+                binding: preset.binding.clone(),
+            })
+            .collect(),
+    });
+    // Leave out bindings not allowed by the policy
+    builtin
+        .chain(installed)
+        .map(|mut category| {
+            category
+                .entries
+                .retain(|entry| policy().binding_allowed(&entry.binding));
+            category
+        })
+        .filter(|category| !category.entries.is_empty())
+        .collect()
+});
 
 pub enum BindingDialogMsg {
-    Show(HardwareButton),
+    // Button, and a custom binding on the mouse that isn't in the profile
+    Show(HardwareButton, Option<Binding>),
     #[allow(unused)]
     Hide,
     SelectCategory(Option<&'static Category>),
     Selected(&'static Entry),
+    CaptureShortcut,
+    ShortcutCaptured(CapturedShortcut),
+    SetShortcut,
+    ImportFromMouse,
 }
 
 pub struct BindingDialogModel {
     button_id: HardwareButton,
     category: Option<&'static Category>,
+    capturing: bool,
+    shortcut: Option<CapturedShortcut>,
+    mouse_binding: Option<Binding>,
     shown: bool,
 }
 
@@ -33,6 +100,9 @@ impl ComponentUpdate<super::AppModel> for BindingDialogModel {
         BindingDialogModel {
             button_id: HardwareButton::Right,
             category: None,
+            capturing: false,
+            shortcut: None,
+            mouse_binding: None,
             shown: false,
         }
     }
@@ -45,9 +115,11 @@ impl ComponentUpdate<super::AppModel> for BindingDialogModel {
         parent_sender: Sender<AppMsg>,
     ) {
         match msg {
-            BindingDialogMsg::Show(button_id) => {
+            BindingDialogMsg::Show(button_id, mouse_binding) => {
                 self.button_id = button_id;
+                self.mouse_binding = mouse_binding;
                 self.category = None;
+                self.capturing = false;
                 self.shown = true;
             }
             BindingDialogMsg::Hide => {
@@ -55,14 +127,37 @@ impl ComponentUpdate<super::AppModel> for BindingDialogModel {
             }
             BindingDialogMsg::SelectCategory(category) => {
                 self.category = category;
+                self.capturing = false;
             }
             BindingDialogMsg::Selected(entry) => {
                 send!(
                     parent_sender,
-                    AppMsg::SetBinding(self.button_id, Binding::Preset(entry.id))
+                    AppMsg::SetBinding(self.button_id, entry.binding.clone())
                 );
                 self.shown = false;
             }
+            BindingDialogMsg::CaptureShortcut => {
+                self.shortcut = None;
+                self.capturing = true;
+            }
+            BindingDialogMsg::ShortcutCaptured(shortcut) => {
+                self.shortcut = Some(shortcut);
+            }
+            BindingDialogMsg::SetShortcut => {
+                if let Some(CapturedShortcut { shortcut, .. }) = self.shortcut {
+                    // Use preset, if shortcut matches one
+                    let binding = Binding::for_action(&shortcut.binding())
+                        .unwrap_or(Binding::Shortcut(shortcut));
+                    send!(parent_sender, AppMsg::SetBinding(self.button_id, binding));
+                    self.shown = false;
+                }
+            }
+            BindingDialogMsg::ImportFromMouse => {
+                if let Some(binding) = self.mouse_binding.take() {
+                    send!(parent_sender, AppMsg::SetBinding(self.button_id, binding));
+                    self.shown = false;
+                }
+            }
         }
     }
 }
@@ -80,12 +175,20 @@ impl Widgets<BindingDialogModel, super::AppModel> for BindingDialogWidgets {
             set_titlebar = Some(&gtk4::HeaderBar) {
                 pack_start = &gtk4::Button {
                     add_css_class: "flat",
-                    set_visible: watch!(model.category.is_some()),
+                    set_visible: watch!(model.category.is_some() || model.capturing),
                     set_icon_name: "go-previous-symbolic",
                     connect_clicked(sender) => move |_| {
                         send!(sender, BindingDialogMsg::SelectCategory(None));
                     }
                 },
+                pack_end = &gtk4::Button {
+                    set_label: "Import from Mouse",
+                    set_visible: watch!(model.mouse_binding.is_some() && model.category.is_none() && !model.capturing),
+                    set_tooltip_text: watch!(model.mouse_binding.as_ref().map(|x| format!("Save “{}”, set on the mouse by another program", x.label())).as_deref()),
+                    connect_clicked(sender) => move |_| {
+                        send!(sender, BindingDialogMsg::ImportFromMouse);
+                    }
+                },
             },
             set_child = Some(&gtk4::ScrolledWindow) {
                 set_hscrollbar_policy: gtk4::PolicyType::Never,
@@ -103,8 +206,12 @@ impl Widgets<BindingDialogModel, super::AppModel> for BindingDialogWidgets {
                         add_css_class: "frame",
                         set_header_func: util::header_func,
                         connect_row_activated(sender) => move |_, row| {
-                            let category = categories[row.index() as usize];
-                            send!(sender, BindingDialogMsg::SelectCategory(Some(category)));
+                            // Last row, after categories, is custom shortcut
+                            if let Some(category) = categories.get(row.index() as usize) {
+                                send!(sender, BindingDialogMsg::SelectCategory(Some(category)));
+                            } else {
+                                send!(sender, BindingDialogMsg::CaptureShortcut);
+                            }
                         },
                     },
                     add_child: binding_vbox = &gtk4::Box {
@@ -124,12 +231,43 @@ impl Widgets<BindingDialogModel, super::AppModel> for BindingDialogWidgets {
                                 let row_category = rows[row.index() as usize].0;
                                 ptr::eq(row_category, category.get())
                             },
-                            connect_row_activated(rows) => move |_, row| {
+                            connect_row_activated(sender, rows) => move |_, row| {
                                 let entry = rows[row.index() as usize].1;
                                 send!(sender, BindingDialogMsg::Selected(entry));
 
                             },
                         },
+                    },
+                    add_child: shortcut_vbox = &gtk4::Box {
+                        set_orientation: gtk4::Orientation::Vertical,
+                        set_spacing: 6,
+                        append = &gtk4::Label {
+                            set_label: "Custom Shortcut",
+                            set_attributes = Some(&pango::AttrList) {
+                                insert: pango::AttrInt::new_weight(pango::Weight::Bold)
+                            },
+                        },
+                        append = &gtk4::Label {
+                            set_label: "Press the key combination to send when the button is clicked.",
+                            set_wrap: true,
+                        },
+                        append = &gtk4::Label {
+                            set_margin_top: 12,
+                            set_margin_bottom: 12,
+                            set_label: watch! { model.shortcut.as_ref().map_or("New Shortcut…", |x| &x.label) },
+                            set_attributes = Some(&pango::AttrList) {
+                                insert: pango::AttrFloat::new_scale(pango::SCALE_LARGE)
+                            },
+                        },
+                        append = &gtk4::Button {
+                            set_halign: gtk4::Align::End,
+                            set_label: "Set",
+                            add_css_class: "suggested-action",
+                            set_sensitive: watch! { model.shortcut.is_some() },
+                            connect_clicked(sender) => move |_| {
+                                send!(sender, BindingDialogMsg::SetShortcut);
+                            }
+                        }
                     }
                 }
             }
@@ -138,12 +276,13 @@ impl Widgets<BindingDialogModel, super::AppModel> for BindingDialogWidgets {
 
     additional_fields! {
         category: Rc<Cell<&'static Category>>,
+        capturing: Rc<Cell<bool>>,
     }
 
     fn pre_init() {
         let mut categories = Vec::new();
         let mut rows = Vec::new();
-        for category in &*BINDINGS {
+        for category in &*CATEGORIES {
             categories.push(category);
             for entry in &category.entries {
                 rows.push((category, entry));
@@ -151,11 +290,12 @@ impl Widgets<BindingDialogModel, super::AppModel> for BindingDialogWidgets {
         }
         let rows = Rc::new(rows);
 
-        let category = Rc::new(Cell::new(&BINDINGS[0]));
+        let category = Rc::new(Cell::new(&CATEGORIES[0]));
+        let capturing = Rc::new(Cell::new(false));
     }
 
     fn post_init() {
-        for category in &*BINDINGS {
+        for category in &*CATEGORIES {
             let mut rows = HashMap::<gtk4::ListBoxRow, &'static Entry>::new();
 
             view! {
@@ -198,7 +338,7 @@ impl Widgets<BindingDialogModel, super::AppModel> for BindingDialogWidgets {
                         }
                     }
                 }
-                if let Some(keybind) = entry.keybind {
+                if let Some(keybind) = &entry.keybind {
                     view! {
                         keybind_label = gtk4::Label {
                             set_label: keybind,
@@ -213,6 +353,48 @@ impl Widgets<BindingDialogModel, super::AppModel> for BindingDialogWidgets {
             }
         }
 
+        view! {
+            shortcut_row = gtk4::ListBoxRow {
+                set_selectable: false,
+                set_child = Some(&gtk4::Box) {
+                    set_margin_top: 6,
+                    set_margin_bottom: 6,
+                    set_margin_start: 6,
+                    set_margin_end: 6,
+                    set_spacing: 12,
+                    set_orientation: gtk4::Orientation::Horizontal,
+                    append = &gtk4::Label {
+                        set_label: "Custom Shortcut",
+                    },
+                    append = &gtk4::Image {
+                        set_hexpand: true,
+                        set_halign: gtk4::Align::End,
+                        set_icon_name: Some("go-next-symbolic"),
+                    }
+                }
+            }
+        }
+        // Custom shortcuts may be disallowed by the policy
+        if policy().shortcuts_allowed() {
+            category_list_box.append(&shortcut_row);
+        }
+
+        // Capture key presses before they activate widgets in the dialog
+        let key_controller = gtk4::EventControllerKey::new();
+        key_controller.set_propagation_phase(gtk4::PropagationPhase::Capture);
+        key_controller.connect_key_pressed(
+            glib::clone!(@strong capturing, @strong sender => move |_, keyval, keycode, state| {
+                if !capturing.get() {
+                    return gtk4::Inhibit(false);
+                }
+                if let Some(shortcut) = shortcut::from_key_event(keycode, keyval, state) {
+                    send!(sender, BindingDialogMsg::ShortcutCaptured(shortcut));
+                }
+                gtk4::Inhibit(true)
+            }),
+        );
+        dialog.add_controller(&key_controller);
+
         // Avoid transition on reopening
         dialog.connect_visible_notify(
             glib::clone!(@strong stack, @strong category_list_box => move |dialog| {
@@ -226,7 +408,10 @@ impl Widgets<BindingDialogModel, super::AppModel> for BindingDialogWidgets {
     }
 
     fn post_view() {
-        if let Some(category) = model.category.as_ref() {
+        self.capturing.set(model.capturing);
+        if model.capturing {
+            self.stack.set_visible_child(&self.shortcut_vbox);
+        } else if let Some(category) = model.category.as_ref() {
             self.stack.set_visible_child(&self.binding_vbox);
             if !ptr::eq(self.category.get(), *category) {
                 self.category.set(*category);
@@ -1,6 +1,6 @@
 use gtk4::{glib, prelude::*, subclass::prelude::*};
 
-use crate::HardwareButton;
+use mouse_configurator::bindings::HardwareButton;
 
 const SVG_WIDTH: f64 = 474.;
 const SVG_HEIGHT: f64 = 347.;
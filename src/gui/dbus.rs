@@ -0,0 +1,302 @@
+//! `org.pop_os.MouseConfigurator` D-Bus interface, for scripting the running app.
+//!
+//! Method calls are forwarded to `AppModel` as `AppMsg::DBusCall`, which
+//! replies once it has handled the request.
+
+use gtk4::{
+    gio,
+    glib::{self, thread_guard::ThreadGuard, ToVariant},
+};
+use relm4::{send, Sender};
+use std::sync::Mutex;
+
+use crate::AppMsg;
+
+pub const INTERFACE: &str = "org.pop_os.MouseConfigurator";
+pub const OBJECT_PATH: &str = "/org/pop_os/MouseConfigurator";
+const ERROR_FAILED: &str = "org.pop_os.MouseConfigurator.Error.Failed";
+const ERROR_INVALID_ARGS: &str = "org.freedesktop.DBus.Error.InvalidArgs";
+
+const INTROSPECTION_XML: &str = r#"
+<node>
+  <interface name="org.pop_os.MouseConfigurator">
+    <!-- Each device has `serial`, `model`, `connected`, `profile`, and `dpi`,
+         and `battery` and `firmware` if known -->
+    <method name="ListDevices">
+      <arg name="devices" type="aa{sv}" direction="out"/>
+    </method>
+    <method name="GetProfile">
+      <arg name="serial" type="s" direction="in"/>
+      <arg name="profile" type="u" direction="out"/>
+    </method>
+    <method name="SetProfile">
+      <arg name="serial" type="s" direction="in"/>
+      <arg name="profile" type="u" direction="in"/>
+    </method>
+    <method name="GetDpi">
+      <arg name="serial" type="s" direction="in"/>
+      <arg name="dpi" type="u" direction="out"/>
+    </method>
+    <method name="SetDpi">
+      <arg name="serial" type="s" direction="in"/>
+      <arg name="dpi" type="u" direction="in"/>
+    </method>
+    <!-- Binding is a preset name like `volume-up`, a key combination like
+         `Ctrl+C`, or `default` -->
+    <method name="SetBinding">
+      <arg name="serial" type="s" direction="in"/>
+      <arg name="button" type="u" direction="in"/>
+      <arg name="binding" type="s" direction="in"/>
+    </method>
+    <signal name="DeviceConnected">
+      <arg name="serial" type="s"/>
+    </signal>
+    <signal name="DeviceDisconnected">
+      <arg name="serial" type="s"/>
+    </signal>
+    <signal name="BatteryChanged">
+      <arg name="serial" type="s"/>
+      <arg name="level" type="y"/>
+    </signal>
+  </interface>
+</node>
+"#;
+
+#[derive(Debug, PartialEq)]
+pub enum DBusRequest {
+    ListDevices,
+    GetProfile(String),
+    SetProfile(String, u32),
+    GetDpi(String),
+    SetDpi(String, u32),
+    SetBinding(String, u32, String),
+}
+
+impl DBusRequest {
+    fn parse(method: &str, parameters: &glib::Variant) -> Option<Self> {
+        Some(match method {
+            "ListDevices" => Self::ListDevices,
+            "GetProfile" => Self::GetProfile(parameters.get::<(String,)>()?.0),
+            "SetProfile" => {
+                let (serial, profile) = parameters.get()?;
+                Self::SetProfile(serial, profile)
+            }
+            "GetDpi" => Self::GetDpi(parameters.get::<(String,)>()?.0),
+            "SetDpi" => {
+                let (serial, dpi) = parameters.get()?;
+                Self::SetDpi(serial, dpi)
+            }
+            "SetBinding" => {
+                let (serial, button, binding) = parameters.get()?;
+                Self::SetBinding(serial, button, binding)
+            }
+            _ => return None,
+        })
+    }
+}
+
+pub struct DBusCall {
+    request: DBusRequest,
+    invocation: ThreadGuard<gio::DBusMethodInvocation>,
+}
+
+impl DBusCall {
+    /// Handle the request, replying with the tuple of out arguments `f`
+    /// returns, or its error message
+    pub fn handle<F: FnOnce(DBusRequest) -> Result<glib::Variant, String>>(self, f: F) {
+        let invocation = self.invocation.into_inner();
+        match f(self.request) {
+            Ok(value) => invocation.return_value(Some(&value)),
+            Err(err) => invocation.return_dbus_error(ERROR_FAILED, &err),
+        }
+    }
+}
+
+/// Export the interface on `connection`, calling `cb` for each method call.
+/// Must be called on the thread running the default main context.
+pub fn register_with<F: Fn(DBusCall) + Send + Sync + 'static>(
+    connection: &gio::DBusConnection,
+    cb: F,
+) -> Result<gio::RegistrationId, glib::Error> {
+    let node_info = gio::DBusNodeInfo::for_xml(INTROSPECTION_XML)?;
+    let interface_info = node_info.lookup_interface(INTERFACE).unwrap();
+    connection.register_object(
+        OBJECT_PATH,
+        &interface_info,
+        move |_, _, _, _, method, parameters, invocation| match DBusRequest::parse(
+            method,
+            &parameters,
+        ) {
+            Some(request) => cb(DBusCall {
+                request,
+                invocation: ThreadGuard::new(invocation),
+            }),
+            None => invocation
+                .return_dbus_error(ERROR_INVALID_ARGS, &format!("Invalid call to `{}`", method)),
+        },
+        |_, _, _, _, _| ().to_variant(),
+        |_, _, _, _, _, _| false,
+    )
+}
+
+pub fn register(
+    connection: &gio::DBusConnection,
+    sender: Sender<AppMsg>,
+) -> Result<gio::RegistrationId, glib::Error> {
+    let sender = Mutex::new(sender);
+    register_with(connection, move |call| {
+        send!(sender.lock().unwrap(), AppMsg::DBusCall(call));
+    })
+}
+
+fn emit(connection: Option<&gio::DBusConnection>, signal: &str, parameters: glib::Variant) {
+    if let Some(connection) = connection {
+        if let Err(err) =
+            connection.emit_signal(None, OBJECT_PATH, INTERFACE, signal, Some(&parameters))
+        {
+            eprintln!("Failed to emit D-Bus signal `{}`: {}", signal, err);
+        }
+    }
+}
+
+pub fn emit_device_connected(connection: Option<&gio::DBusConnection>, serial: &str) {
+    emit(connection, "DeviceConnected", (serial,).to_variant());
+}
+
+pub fn emit_device_disconnected(connection: Option<&gio::DBusConnection>, serial: &str) {
+    emit(connection, "DeviceDisconnected", (serial,).to_variant());
+}
+
+pub fn emit_battery_changed(connection: Option<&gio::DBusConnection>, serial: &str, level: u8) {
+    emit(connection, "BatteryChanged", (serial, level).to_variant());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::RefCell,
+        io::{BufRead, BufReader},
+        process::{Child, Command, Stdio},
+        rc::Rc,
+        thread,
+    };
+
+    use super::*;
+
+    // Kills the bus daemon when dropped, including if the test panics
+    struct DaemonGuard(Child);
+
+    impl Drop for DaemonGuard {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    fn connect(address: &str) -> gio::DBusConnection {
+        gio::DBusConnection::for_address_sync(
+            address,
+            gio::DBusConnectionFlags::AUTHENTICATION_CLIENT
+                | gio::DBusConnectionFlags::MESSAGE_BUS_CONNECTION,
+            None,
+            None::<&gio::Cancellable>,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn private_bus() {
+        // Run a private session bus, if `dbus-daemon` is installed
+        let mut daemon = match Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .spawn()
+        {
+            Ok(daemon) => DaemonGuard(daemon),
+            Err(_) => {
+                eprintln!("`dbus-daemon` not found; skipping");
+                return;
+            }
+        };
+        let mut address = String::new();
+        BufReader::new(daemon.0.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        let address = address.trim().to_string();
+
+        let context = glib::MainContext::new();
+        context
+            .with_thread_default(|| {
+                let server = connect(&address);
+                register_with(&server, |call| {
+                    call.handle(|request| match request {
+                        DBusRequest::GetDpi(serial) if serial == "ABC" => {
+                            Ok((1200u32,).to_variant())
+                        }
+                        _ => Err("No such device".to_string()),
+                    })
+                })
+                .unwrap();
+                let name = server.unique_name().unwrap().to_string();
+
+                // Sending on a channel attached to the context wakes it, unlike
+                // a `std::sync::mpsc` channel
+                let (result_sender, result_receiver) =
+                    glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+                let result = Rc::new(RefCell::new(None));
+                result_receiver.attach(
+                    Some(&context),
+                    glib::clone!(@strong result => move |res| {
+                        *result.borrow_mut() = Some(res);
+                        glib::Continue(false)
+                    }),
+                );
+                thread::spawn(move || {
+                    let client = connect(&address);
+                    let call = |serial: &str| {
+                        client
+                            .call_sync(
+                                Some(&name),
+                                OBJECT_PATH,
+                                INTERFACE,
+                                "GetDpi",
+                                Some(&(serial,).to_variant()),
+                                None,
+                                gio::DBusCallFlags::NONE,
+                                5000,
+                                None::<&gio::Cancellable>,
+                            )
+                            .map_err(|err| err.to_string())
+                            .and_then(|x| {
+                                x.get::<(u32,)>()
+                                    .map(|x| x.0)
+                                    .ok_or_else(|| format!("Invalid reply: {}", x))
+                            })
+                    };
+                    result_sender.send((call("ABC"), call("XYZ"))).unwrap();
+                });
+
+                // Calls time out, so the thread always sends a result
+                while result.borrow().is_none() {
+                    context.iteration(true);
+                }
+                let (found, missing) = result.take().unwrap();
+                assert_eq!(found, Ok(1200));
+                assert!(missing.unwrap_err().contains("No such device"));
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn parse_request() {
+        assert_eq!(
+            DBusRequest::parse("SetBinding", &("ABC", 3u32, "Ctrl+C").to_variant()),
+            Some(DBusRequest::SetBinding(
+                "ABC".to_string(),
+                3,
+                "Ctrl+C".to_string()
+            ))
+        );
+        assert_eq!(DBusRequest::parse("SetDpi", &("ABC",).to_variant()), None);
+    }
+}
@@ -1,37 +1,148 @@
-//! Use a separate process, started with `pkexec`, to monitor for devices
-//! with udev and open them. Using `pkexec` allows it to access the device
-//! without a persistent daemon or udev rule.
-
-use nix::{
-    cmsg_space,
-    errno::Errno,
-    sys::socket::{
-        recvmsg, sendmsg, socketpair, AddressFamily, ControlMessage, ControlMessageOwned, MsgFlags,
-        SockFlag, SockType, UnixAddr,
-    },
-};
+//! Use a separate process, `mouse-configurator-helper` started with
+//! `pkexec`, to monitor for devices with udev and open them. Using `pkexec`
+//! allows it to access the device without a persistent daemon or udev rule.
+//!
+//! If the user can already open the devices, like with the `uaccess` udev rule
+//! we ship, they are opened in-process instead.
+
+use nix::sys::socket::{socketpair, AddressFamily, SockFlag, SockType};
 use std::{
-    env,
-    ffi::OsStr,
-    io::{self, IoSlice, IoSliceMut},
-    os::unix::{
-        ffi::OsStrExt,
-        io::{AsRawFd, FromRawFd, RawFd},
-    },
+    env, io,
+    os::unix::io::{FromRawFd, RawFd},
     path::{Path, PathBuf},
-    process::{Command, Stdio},
+    process::{Child, Command, Stdio},
+};
+
+use mouse_configurator::{
+    monitor_protocol::{recv_message, MonitorMessage, PROTOCOL_VERSION},
+    HpMouse, MonitorEvent,
+};
+
+// Set by `make install`, to match the polkit policy
+#[cfg(not(feature = "appimage"))]
+const HELPER_PATH: &str = match option_env!("MOUSE_CONFIGURATOR_HELPER") {
+    Some(path) => path,
+    None => "/usr/libexec/mouse-configurator-helper",
 };
 
-use mouse_configurator::HpMouse;
+// Installed by `make install`, or by a distribution package
+const UDEV_RULE_PATHS: &[&str] = &[
+    "/etc/udev/rules.d/70-mouse-configurator.rules",
+    "/usr/lib/udev/rules.d/70-mouse-configurator.rules",
+    "/lib/udev/rules.d/70-mouse-configurator.rules",
+];
+
+pub enum DeviceEvent {
+    Added(PathBuf, HpMouse),
+    // May not be a device that was added
+    Removed(PathBuf),
+    Changed(PathBuf),
+    OpenFailed(PathBuf, io::Error),
+}
+
+pub type DeviceIter = Box<dyn Iterator<Item = io::Result<DeviceEvent>>>;
+
+/// Source of newly connected devices
+pub enum DeviceMonitor {
+    Process(Box<DeviceMonitorProcess>),
+    // The udev monitor isn't `Send`, so it is created by `devices`
+    InProcess,
+}
+
+impl DeviceMonitor {
+    /// Iterate over devices as they are connected, and opened. Call on the
+    /// thread that will use the iterator.
+    pub fn devices(self) -> io::Result<DeviceIter> {
+        match self {
+            Self::Process(process) => Ok(process),
+            Self::InProcess => in_process_monitor(),
+        }
+    }
+}
+
+// Test if the devices can be opened without `pkexec`. If none are connected,
+// assume they can if running as root, or if the udev rule is installed.
+fn has_hidraw_access() -> bool {
+    if nix::unistd::geteuid().is_root() {
+        return true;
+    }
+    match mouse_configurator::enumerate() {
+        Ok(devices) if !devices.is_empty() => devices.iter().all(|x| x.open().is_ok()),
+        Ok(_) => UDEV_RULE_PATHS.iter().any(|x| Path::new(x).exists()),
+        Err(_) => false,
+    }
+}
+
+fn in_process_monitor() -> io::Result<DeviceIter> {
+    // Start monitoring first, so devices added while enumerating aren't missed
+    let monitor_events = mouse_configurator::monitor()?;
+    let current_devices = mouse_configurator::enumerate()?;
+    Ok(Box::new(
+        current_devices
+            .into_iter()
+            .map(MonitorEvent::Added)
+            .chain(monitor_events)
+            .map(|event| {
+                Ok(match event {
+                    MonitorEvent::Added(device_info) => match device_info.open() {
+                        Ok(mouse) => DeviceEvent::Added(device_info.devnode, mouse),
+                        Err(err) => DeviceEvent::OpenFailed(device_info.devnode, err),
+                    },
+                    MonitorEvent::Removed(devnode) => DeviceEvent::Removed(devnode),
+                    MonitorEvent::Changed(device_info) => DeviceEvent::Changed(device_info.devnode),
+                })
+            }),
+    ))
+}
+
+/// Monitor devices in-process if possible, or with `pkexec` otherwise
+pub fn open_device_monitor() -> io::Result<DeviceMonitor> {
+    if has_hidraw_access() {
+        Ok(DeviceMonitor::InProcess)
+    } else {
+        Ok(DeviceMonitor::Process(Box::new(
+            DeviceMonitorProcess::new()?
+        )))
+    }
+}
+
+// The AppImage can't install a helper, so it runs itself with `--device-monitor`
+#[cfg(feature = "appimage")]
+fn helper_command() -> Command {
+    let appimage = env::var("APPIMAGE").expect("Failed to get executable path");
+    let mut command = Command::new("pkexec");
+    command.arg(appimage).arg("--device-monitor");
+    command
+}
+
+// Use the installed helper, or the one built alongside the GUI when running
+// from the build directory
+#[cfg(not(feature = "appimage"))]
+fn helper_command() -> Command {
+    let path = env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.join("mouse-configurator-helper")))
+        .filter(|path| path.exists() && !Path::new(HELPER_PATH).exists())
+        .unwrap_or_else(|| PathBuf::from(HELPER_PATH));
+    let mut command = Command::new("pkexec");
+    command.arg(path);
+    command
+}
 
 pub struct DeviceMonitorProcess {
+    child: Child,
     sock: RawFd,
-    buf: [u8; 1024],
+    buf: Vec<u8>,
+    // Set once the monitor has sent `Shutdown`, or the socket failed
+    finished: bool,
 }
 
 impl Drop for DeviceMonitorProcess {
     fn drop(&mut self) {
         let _ = nix::unistd::close(self.sock);
+        // Reap the process if it has exited. Otherwise it exits once it sees
+        // the socket is closed.
+        let _ = self.child.try_wait();
     }
 }
 
@@ -43,110 +154,87 @@ impl DeviceMonitorProcess {
             None,
             SockFlag::SOCK_CLOEXEC,
         )?;
-        let command_path = if cfg!(feature = "appimage") {
-            PathBuf::from(env::var("APPIMAGE").expect("Failed to get executable path"))
-        } else {
-            env::current_exe().expect("Failed to get executable path")
+        let stdin = unsafe { Stdio::from_raw_fd(sock1) };
+        let child = helper_command().stdin(stdin).spawn()?;
+
+        let mut monitor = Self {
+            child,
+            sock: sock2,
+            buf: vec![0; 4096],
+            finished: false,
         };
 
-        let stdin = unsafe { Stdio::from_raw_fd(sock1) };
-        let mut child = Command::new("pkexec")
-            .arg(command_path)
-            .arg("--device-monitor")
-            .stdin(stdin)
-            .spawn()?;
-
-        let mut buf = [0; 1024];
-
-        loop {
-            match nix::unistd::read(sock2, &mut buf) {
-                Err(Errno::EINTR) => {}
-                Ok(0) | Err(Errno::EPIPE) => {
-                    let status = child.wait()?;
-                    let message = format!("Pkexec process failed: {}", status);
-                    return Err(io::Error::new(io::ErrorKind::Other, message));
-                }
-                Err(err) => {
-                    return Err(err.into());
-                }
-                Ok(x) => {
-                    break;
-                }
+        match recv_message(monitor.sock, &mut monitor.buf)? {
+            Some((MonitorMessage::Hello { version }, _)) if version == PROTOCOL_VERSION => {}
+            Some((MonitorMessage::Hello { version }, _)) => {
+                let message = format!(
+                    "Device monitor uses protocol version {}, but {} is required",
+                    version, PROTOCOL_VERSION
+                );
+                return Err(io::Error::new(io::ErrorKind::Other, message));
+            }
+            Some((MonitorMessage::Shutdown { reason }, _)) => {
+                let message = format!("Device monitor failed to start: {}", reason);
+                return Err(io::Error::new(io::ErrorKind::Other, message));
+            }
+            Some((message, _)) => {
+                let message = format!("Expected hello from device monitor, got {:?}", message);
+                return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+            }
+            None => {
+                let status = monitor.child.wait()?;
+                let message = format!("Pkexec process failed: {}", status);
+                return Err(io::Error::new(io::ErrorKind::Other, message));
             }
         }
 
-        Ok(Self { sock: sock2, buf })
+        Ok(monitor)
     }
 }
 
 impl Iterator for DeviceMonitorProcess {
-    type Item = io::Result<(PathBuf, HpMouse)>;
-
-    fn next(&mut self) -> Option<io::Result<(PathBuf, HpMouse)>> {
-        loop {
-            let mut iov = [IoSliceMut::new(&mut self.buf)];
-            let mut cmsg = cmsg_space!(RawFd);
-            match recvmsg::<UnixAddr>(self.sock, &mut iov, Some(&mut cmsg), MsgFlags::empty()) {
-                Ok(msg) => {
-                    for cmsg in msg.cmsgs() {
-                        if let ControlMessageOwned::ScmRights(fds) = cmsg {
-                            assert_eq!(fds.len(), 1);
-                            let fd = fds[0];
-                            let path =
-                                Path::new(OsStr::from_bytes(&self.buf[..msg.bytes])).to_owned();
-                            let mouse = unsafe { HpMouse::from_raw_fd(fd) };
-                            return Some(Ok((path, mouse)));
-                        } else {
-                            panic!("Unexpected control message: {:?}", cmsg);
-                        }
-                    }
-                }
-                Err(Errno::EINTR) => {}
-                Err(err) => {
-                    return Some(Err(err.into()));
-                }
-            }
-        }
-    }
-}
+    type Item = io::Result<DeviceEvent>;
 
-pub fn device_monitor_process() {
-    let current_devices = mouse_configurator::enumerate().unwrap();
-    let monitor_devices = mouse_configurator::monitor().unwrap();
-
-    while nix::unistd::write(libc::STDIN_FILENO, b"Started\n") == Err(Errno::EINTR) {}
-
-    for device_info in current_devices.into_iter().chain(monitor_devices) {
-        match device_info.open() {
-            Ok(device) => {
-                let path = device_info.devnode.as_os_str().as_bytes();
-                let fds = &[device.as_raw_fd()];
-                let iov = &[IoSlice::new(path)];
-                let cmsgs = &[ControlMessage::ScmRights(fds)];
-                loop {
-                    let res = sendmsg(
-                        libc::STDIN_FILENO,
-                        iov,
-                        cmsgs,
-                        MsgFlags::empty(),
-                        None::<&UnixAddr>,
-                    );
-                    match res {
-                        Ok(_) => {
-                            break;
-                        }
-                        Err(Errno::EINTR) => {}
-                        Err(Errno::EPIPE) => {
-                            return;
-                        }
-                        Err(err) => {
-                            eprintln!("Error writing to socket: {}", err);
-                            break;
-                        }
-                    }
-                }
-            }
-            Err(err) => eprintln!("Failed to open device: {}", err),
+    fn next(&mut self) -> Option<io::Result<DeviceEvent>> {
+        if self.finished {
+            return None;
         }
+        let (message, fd) = match recv_message(self.sock, &mut self.buf) {
+            Ok(Some(res)) => res,
+            Ok(None) => {
+                self.finished = true;
+                let message = match self.child.wait() {
+                    Ok(status) => format!("Device monitor exited unexpectedly ({})", status),
+                    Err(_) => "Device monitor exited unexpectedly".to_string(),
+                };
+                return Some(Err(io::Error::new(io::ErrorKind::UnexpectedEof, message)));
+            }
+            // Recoverable, since packet boundaries are preserved
+            Err(err) if err.kind() == io::ErrorKind::InvalidData => return Some(Err(err)),
+            Err(err) => {
+                self.finished = true;
+                return Some(Err(err));
+            }
+        };
+        Some(Ok(match message {
+            MonitorMessage::DeviceAdded { path } => {
+                let mouse = unsafe { HpMouse::from_raw_fd(fd.unwrap()) };
+                DeviceEvent::Added(path, mouse)
+            }
+            MonitorMessage::DeviceRemoved { path } => DeviceEvent::Removed(path),
+            MonitorMessage::DeviceChanged { path } => DeviceEvent::Changed(path),
+            MonitorMessage::OpenFailed { path, errno } => {
+                DeviceEvent::OpenFailed(path, io::Error::from_raw_os_error(errno))
+            }
+            MonitorMessage::Shutdown { reason } => {
+                self.finished = true;
+                let message = format!("Device monitor stopped: {}", reason);
+                return Some(Err(io::Error::new(io::ErrorKind::Other, message)));
+            }
+            MonitorMessage::Hello { .. } => {
+                let message = "Unexpected hello from device monitor";
+                return Some(Err(io::Error::new(io::ErrorKind::InvalidData, message)));
+            }
+        }))
     }
 }
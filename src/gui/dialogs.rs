@@ -1,8 +1,9 @@
-use gtk4::prelude::*;
+use gtk4::{cairo, prelude::*};
 use relm4::view;
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
 use crate::util;
+use mouse_configurator::battery_history::{self, Reading};
 
 pub fn show_about_dialog(main_window: &gtk4::ApplicationWindow) {
     gtk4::AboutDialog::builder()
@@ -24,12 +25,66 @@ fn device_to_model(device: &str) -> &str {
     }
 }
 
+fn format_duration(duration: Duration) -> String {
+    let hours = duration.as_secs() / 3600;
+    if hours >= 48 {
+        format!("About {} days", hours / 24)
+    } else if hours >= 1 {
+        format!("About {} hours", hours)
+    } else {
+        "Less than an hour".to_string()
+    }
+}
+
+// Plot battery level over time, from the first reading to the last
+fn draw_battery_history(
+    area: &gtk4::DrawingArea,
+    cr: &cairo::Context,
+    width: i32,
+    height: i32,
+    readings: &[Reading],
+) {
+    let (first, last) = match (readings.first(), readings.last()) {
+        (Some(first), Some(last)) => (first.time, last.time),
+        _ => return,
+    };
+    let (width, height) = (f64::from(width), f64::from(height));
+    let span = last.saturating_sub(first).max(1) as f64;
+    let x = |time: u64| time.saturating_sub(first) as f64 / span * (width - 2.) + 1.;
+    let y = |level: u8| (1. - f64::from(level.min(100)) / 100.) * (height - 2.) + 1.;
+
+    let color = area.style_context().color();
+    cr.set_line_width(1.);
+    cr.set_source_rgba(
+        color.red().into(),
+        color.green().into(),
+        color.blue().into(),
+        0.3,
+    );
+    cr.rectangle(0.5, 0.5, width - 1., height - 1.);
+    let _ = cr.stroke();
+
+    cr.set_line_width(2.);
+    cr.set_source_rgba(
+        color.red().into(),
+        color.green().into(),
+        color.blue().into(),
+        color.alpha().into(),
+    );
+    for reading in readings {
+        cr.line_to(x(reading.time), y(reading.level));
+    }
+    let _ = cr.stroke();
+}
+
 pub fn show_info_dialog(
     main_window: &gtk4::ApplicationWindow,
     device: &str,
     serial: &str,
     firmware_version: Option<(u16, u16, u16)>,
+    battery_history: Vec<Reading>,
 ) {
+    let remaining = battery_history::estimate_remaining(&battery_history);
     view! {
         dialog = gtk4::Dialog {
             set_transient_for: Some(main_window),
@@ -102,10 +157,55 @@ pub fn show_info_dialog(
                             set_halign: gtk4::Align::End,
                         }
                     }
+                },
+                append = &gtk4::ListBoxRow {
+                    set_visible: remaining.is_some(),
+                    set_margin_start: 6,
+                    set_margin_end: 6,
+                    set_margin_top: 6,
+                    set_margin_bottom: 6,
+                    set_selectable: false,
+                    set_activatable: false,
+                    set_child = Some(&gtk4::Box) {
+                        set_spacing: 12,
+                        set_orientation: gtk4::Orientation::Horizontal,
+                        append = &gtk4::Label {
+                            set_label: "Estimated Battery Life"
+                        },
+                        append = &gtk4::Label {
+                            set_label: &remaining.map_or_else(String::new, format_duration),
+                            set_hexpand: true,
+                            set_halign: gtk4::Align::End,
+                        }
+                    }
+                },
+                append = &gtk4::ListBoxRow {
+                    set_visible: battery_history.len() > 1,
+                    set_margin_start: 6,
+                    set_margin_end: 6,
+                    set_margin_top: 6,
+                    set_margin_bottom: 6,
+                    set_selectable: false,
+                    set_activatable: false,
+                    set_child = Some(&gtk4::Box) {
+                        set_spacing: 6,
+                        set_orientation: gtk4::Orientation::Vertical,
+                        append = &gtk4::Label {
+                            set_label: "Battery History",
+                            set_halign: gtk4::Align::Start,
+                        },
+                        append: chart = &gtk4::DrawingArea {
+                            set_content_width: 320,
+                            set_content_height: 80,
+                        }
+                    }
                 }
             }
         }
     }
+    chart.set_draw_func(move |area, cr, width, height| {
+        draw_battery_history(area, cr, width, height, &battery_history);
+    });
     dialog.show();
 }
 
@@ -0,0 +1,311 @@
+//! Tracking of the focused application, used to select profiles by rule.
+
+use relm4::Sender;
+use std::{
+    env,
+    ffi::OsStr,
+    io::{self, BufRead, BufReader, Read, Write},
+    os::unix::net::UnixStream,
+    process::{Child, ChildStdout, Command, Stdio},
+    thread,
+};
+
+use crate::AppMsg;
+
+pub trait FocusSource: Send {
+    /// Block until the focused window changes. Returns identifiers for the
+    /// application that now has focus: its app-id, or the instance and class
+    /// from `WM_CLASS`. Empty if no window has focus.
+    fn next_focus(&mut self) -> io::Result<Vec<String>>;
+}
+
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+// i3 IPC, also implemented by Sway
+const I3_IPC_MAGIC: &[u8] = b"i3-ipc";
+const I3_IPC_SUBSCRIBE: u32 = 2;
+const I3_IPC_GET_TREE: u32 = 4;
+const I3_IPC_EVENT_WINDOW: u32 = 0x80000003;
+
+pub struct SwayFocusSource {
+    stream: UnixStream,
+    initial: Option<Vec<String>>,
+}
+
+impl SwayFocusSource {
+    pub fn connect<P: AsRef<OsStr>>(path: P) -> io::Result<Self> {
+        let mut stream = UnixStream::connect(path.as_ref())?;
+
+        // Query before subscribing, so the reply isn't interleaved with events
+        i3_ipc_send(&mut stream, I3_IPC_GET_TREE, b"")?;
+        let (_, tree) = i3_ipc_recv(&mut stream)?;
+        let tree: serde_json::Value = serde_json::from_slice(&tree).map_err(invalid_data)?;
+        let initial = find_focused(&tree).map_or_else(Vec::new, container_app_ids);
+
+        i3_ipc_send(&mut stream, I3_IPC_SUBSCRIBE, br#"["window"]"#)?;
+        let (_, reply) = i3_ipc_recv(&mut stream)?;
+        let reply: serde_json::Value = serde_json::from_slice(&reply).map_err(invalid_data)?;
+        if reply["success"] != true {
+            return Err(invalid_data("Failed to subscribe to window events"));
+        }
+
+        Ok(Self {
+            stream,
+            initial: Some(initial),
+        })
+    }
+}
+
+impl FocusSource for SwayFocusSource {
+    fn next_focus(&mut self) -> io::Result<Vec<String>> {
+        if let Some(initial) = self.initial.take() {
+            return Ok(initial);
+        }
+        loop {
+            let (kind, payload) = i3_ipc_recv(&mut self.stream)?;
+            if kind != I3_IPC_EVENT_WINDOW {
+                continue;
+            }
+            let event: serde_json::Value =
+                serde_json::from_slice(&payload).map_err(invalid_data)?;
+            if event["change"] == "focus" {
+                return Ok(container_app_ids(&event["container"]));
+            }
+        }
+    }
+}
+
+fn i3_ipc_send(stream: &mut UnixStream, kind: u32, payload: &[u8]) -> io::Result<()> {
+    let mut msg = I3_IPC_MAGIC.to_vec();
+    msg.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+    msg.extend_from_slice(&kind.to_ne_bytes());
+    msg.extend_from_slice(payload);
+    stream.write_all(&msg)
+}
+
+fn i3_ipc_recv(stream: &mut UnixStream) -> io::Result<(u32, Vec<u8>)> {
+    let mut header = [0; 14];
+    stream.read_exact(&mut header)?;
+    if &header[..6] != I3_IPC_MAGIC {
+        return Err(invalid_data("Invalid i3 IPC header"));
+    }
+    let len = u32::from_ne_bytes(header[6..10].try_into().unwrap());
+    let kind = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+    let mut payload = vec![0; len as usize];
+    stream.read_exact(&mut payload)?;
+    Ok((kind, payload))
+}
+
+fn find_focused(node: &serde_json::Value) -> Option<&serde_json::Value> {
+    if node["focused"] == true {
+        return Some(node);
+    }
+    ["nodes", "floating_nodes"]
+        .iter()
+        .filter_map(|key| node[key].as_array())
+        .flatten()
+        .find_map(find_focused)
+}
+
+fn container_app_ids(container: &serde_json::Value) -> Vec<String> {
+    [
+        &container["app_id"],
+        &container["window_properties"]["instance"],
+        &container["window_properties"]["class"],
+    ]
+    .iter()
+    .filter_map(|x| x.as_str())
+    .map(str::to_string)
+    .collect()
+}
+
+// Uses `xprop`, rather than adding an X11 client library. Under Wayland
+// compositors other than Sway, this still works for XWayland windows.
+pub struct X11FocusSource {
+    child: Child,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl X11FocusSource {
+    pub fn spawn() -> io::Result<Self> {
+        let mut child = Command::new("xprop")
+            .args(["-root", "-spy", "_NET_ACTIVE_WINDOW"])
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Ok(Self { child, stdout })
+    }
+}
+
+impl FocusSource for X11FocusSource {
+    fn next_focus(&mut self) -> io::Result<Vec<String>> {
+        let mut line = String::new();
+        if self.stdout.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "`xprop` exited",
+            ));
+        }
+        let window = match parse_active_window(&line) {
+            Some(window) if window != 0 => window,
+            _ => return Ok(Vec::new()),
+        };
+        let output = Command::new("xprop")
+            .args(["-id", &format!("{:#x}", window), "WM_CLASS"])
+            .stderr(Stdio::null())
+            .output()?;
+        Ok(parse_wm_class(&String::from_utf8_lossy(&output.stdout)))
+    }
+}
+
+impl Drop for X11FocusSource {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// Parse `_NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00003`
+fn parse_active_window(line: &str) -> Option<u32> {
+    let (_, id) = line.split_once('#')?;
+    let id = id
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .find(|x| !x.is_empty())?;
+    u32::from_str_radix(id.strip_prefix("0x")?, 16).ok()
+}
+
+// Parse `WM_CLASS(STRING) = "Navigator", "firefox"`
+fn parse_wm_class(output: &str) -> Vec<String> {
+    let (_, value) = match output.split_once('=') {
+        Some(value) => value,
+        None => return Vec::new(),
+    };
+    value
+        .split('"')
+        .skip(1)
+        .step_by(2)
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+pub struct FakeFocusSource(pub std::sync::mpsc::Receiver<Vec<String>>);
+
+#[cfg(test)]
+impl FocusSource for FakeFocusSource {
+    fn next_focus(&mut self) -> io::Result<Vec<String>> {
+        self.0
+            .recv()
+            .map_err(|err| io::Error::new(io::ErrorKind::BrokenPipe, err))
+    }
+}
+
+pub fn default_source() -> io::Result<Box<dyn FocusSource>> {
+    for var in ["SWAYSOCK", "I3SOCK"] {
+        if let Some(path) = env::var_os(var) {
+            return Ok(Box::new(SwayFocusSource::connect(path)?));
+        }
+    }
+    if env::var_os("DISPLAY").is_some() {
+        return Ok(Box::new(X11FocusSource::spawn()?));
+    }
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "no supported way to track focus in this session",
+    ))
+}
+
+// Call `cb` each time focus moves to a different application, until the source
+// fails or `cb` returns `false`.
+fn track(source: &mut dyn FocusSource, mut cb: impl FnMut(Vec<String>) -> bool) -> io::Result<()> {
+    let mut focused = None;
+    loop {
+        let app_ids = source.next_focus()?;
+        if focused.as_ref() != Some(&app_ids) {
+            focused = Some(app_ids.clone());
+            if !cb(app_ids) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+pub fn spawn_focus_thread(sender: Sender<AppMsg>) {
+    thread::spawn(move || {
+        let res = default_source().and_then(|mut source| {
+            track(&mut *source, |app_ids| {
+                sender.send(AppMsg::FocusChanged(app_ids)).is_ok()
+            })
+        });
+        if let Err(err) = res {
+            eprintln!("Focus tracking unavailable: {}", err);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+
+    #[test]
+    fn track_fake_source() {
+        let (sender, receiver) = mpsc::channel();
+        let mut source = FakeFocusSource(receiver);
+        for app_ids in [&["krita"][..], &["krita"], &[], &["firefox"]] {
+            sender
+                .send(app_ids.iter().map(|x| x.to_string()).collect())
+                .unwrap();
+        }
+        drop(sender);
+
+        let mut changes = Vec::new();
+        let res = track(&mut source, |app_ids| {
+            changes.push(app_ids);
+            true
+        });
+        assert!(res.is_err());
+        assert_eq!(
+            changes,
+            vec![
+                vec!["krita".to_string()],
+                vec![],
+                vec!["firefox".to_string()]
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_xprop() {
+        assert_eq!(
+            parse_active_window("_NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00003\n"),
+            Some(0x3a00003)
+        );
+        assert_eq!(
+            parse_wm_class("WM_CLASS(STRING) = \"Navigator\", \"firefox\"\n"),
+            vec!["Navigator".to_string(), "firefox".to_string()]
+        );
+        assert!(parse_wm_class("WM_CLASS:  not found.\n").is_empty());
+    }
+
+    #[test]
+    fn sway_focused_container() {
+        let tree = serde_json::json!({
+            "focused": false,
+            "nodes": [{
+                "focused": false,
+                "nodes": [],
+                "floating_nodes": [{
+                    "focused": true,
+                    "app_id": null,
+                    "window_properties": { "instance": "krita", "class": "krita" },
+                }],
+            }],
+        });
+        let focused = find_focused(&tree).unwrap();
+        assert_eq!(container_app_ids(focused), vec!["krita", "krita"]);
+    }
+}
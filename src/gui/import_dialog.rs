@@ -0,0 +1,255 @@
+use gtk4::prelude::*;
+use relm4::{send, ComponentUpdate, Model, Sender, Widgets};
+
+use crate::AppMsg;
+use mouse_configurator::config::{MouseConfig, Profile};
+
+/// Part of an imported config to apply to the device
+pub enum ImportSelection {
+    All(MouseConfig),
+    // Profile, and the slot to replace, or `None` to add it
+    Profile(Profile, Option<usize>),
+}
+
+pub enum ImportDialogMsg {
+    // Current config, and the config to import
+    Show(Box<MouseConfig>, Box<MouseConfig>),
+    // `0` for all profiles, or the index of the profile plus one
+    SetSource(usize),
+    // Slot to replace, or the number of profiles to add a new one
+    SetTarget(usize),
+    Import,
+    Close,
+}
+
+#[derive(Default)]
+pub struct ImportDialogModel {
+    shown: bool,
+    current: Option<MouseConfig>,
+    imported: Option<MouseConfig>,
+    source: usize,
+    target: usize,
+    // Set when a new config is shown, to update dropdowns
+    reset: bool,
+}
+
+impl ImportDialogModel {
+    fn selection(&self) -> Option<ImportSelection> {
+        let imported = self.imported.as_ref()?;
+        let current = self.current.as_ref()?;
+        if self.source == 0 {
+            return Some(ImportSelection::All(imported.clone()));
+        }
+        let profile = imported.profiles().get(self.source - 1)?.clone();
+        let slot = Some(self.target).filter(|x| *x < current.profiles().len());
+        Some(ImportSelection::Profile(profile, slot))
+    }
+
+    fn preview(&self) -> String {
+        let (current, imported) = match (&self.current, &self.imported) {
+            (Some(current), Some(imported)) => (current, imported),
+            _ => return String::new(),
+        };
+        let labels = current.profile_labels();
+        let imported_labels = imported.profile_labels();
+
+        let mut lines = Vec::new();
+        match self.selection() {
+            Some(ImportSelection::All(_)) => {
+                let len = current.profiles().len().max(imported.profiles().len());
+                for i in 0..len {
+                    match (current.profiles().get(i), imported.profiles().get(i)) {
+                        (Some(old), Some(new)) => {
+                            let changes = old.changes(new);
+                            if !changes.is_empty() {
+                                lines.push(format!("{}:", labels[i]));
+                                lines.extend(changes.into_iter().map(|x| format!("  {}", x)));
+                            }
+                        }
+                        (Some(_), None) => lines.push(format!("Removes {}", labels[i])),
+                        (None, Some(_)) => lines.push(format!("Adds {}", imported_labels[i])),
+                        (None, None) => {}
+                    }
+                }
+                if current.rules != imported.rules {
+                    lines.push("Replaces application rules".to_string());
+                }
+            }
+            Some(ImportSelection::Profile(profile, Some(slot))) => {
+                lines = current.profiles()[slot].changes(&profile);
+            }
+            Some(ImportSelection::Profile(_, None)) => {
+                lines.push(format!(
+                    "Adds {} as a new configuration",
+                    imported_labels[self.source - 1]
+                ));
+            }
+            None => {}
+        }
+        if lines.is_empty() {
+            "No changes".to_string()
+        } else {
+            lines.join("\n")
+        }
+    }
+}
+
+impl Model for ImportDialogModel {
+    type Msg = ImportDialogMsg;
+    type Widgets = ImportDialogWidgets;
+    type Components = ();
+}
+
+impl ComponentUpdate<super::AppModel> for ImportDialogModel {
+    fn init_model(_parent_model: &super::AppModel) -> Self {
+        ImportDialogModel::default()
+    }
+
+    fn update(
+        &mut self,
+        msg: ImportDialogMsg,
+        _components: &(),
+        _sender: Sender<ImportDialogMsg>,
+        parent_sender: Sender<AppMsg>,
+    ) {
+        self.reset = false;
+
+        match msg {
+            ImportDialogMsg::Show(current, imported) => {
+                // Import a single profile into a new slot, rather than replacing
+                // every profile with it
+                self.source = if imported.profiles().len() == 1 { 1 } else { 0 };
+                // Add as a new profile by default, rather than replacing one
+                self.target = current.profiles().len();
+                self.current = Some(*current);
+                self.imported = Some(*imported);
+                self.reset = true;
+                self.shown = true;
+            }
+            ImportDialogMsg::SetSource(source) => {
+                self.source = source;
+            }
+            ImportDialogMsg::SetTarget(target) => {
+                self.target = target;
+            }
+            ImportDialogMsg::Import => {
+                if let Some(selection) = self.selection() {
+                    send!(parent_sender, AppMsg::ApplyImport(selection));
+                }
+                self.shown = false;
+            }
+            ImportDialogMsg::Close => {
+                self.shown = false;
+            }
+        }
+    }
+}
+
+#[relm4::widget(pub)]
+impl Widgets<ImportDialogModel, super::AppModel> for ImportDialogWidgets {
+    view! {
+        gtk4::Dialog {
+            set_transient_for: parent!(Some(&parent_widgets.main_window)),
+            set_default_size: args!(400, 300),
+            set_modal: true,
+            set_hide_on_close: true,
+            set_title: Some("Import Configuration"),
+            set_visible: watch!(model.shown),
+            connect_close_request(sender) => move |_| {
+                send!(sender, ImportDialogMsg::Close);
+                gtk4::Inhibit(false)
+            },
+            set_child = Some(&gtk4::Box) {
+                set_orientation: gtk4::Orientation::Vertical,
+                set_margin_start: 12,
+                set_margin_end: 12,
+                set_margin_top: 12,
+                set_margin_bottom: 12,
+                set_spacing: 12,
+                append = &gtk4::Grid {
+                    set_row_spacing: 6,
+                    set_column_spacing: 12,
+                    attach(0, 0, 1, 1) = &gtk4::Label {
+                        set_label: "Import",
+                        set_halign: gtk4::Align::Start,
+                    },
+                    attach(1, 0, 1, 1): source_dropdown = &gtk4::DropDown {
+                        set_hexpand: true,
+                        connect_selected_notify(sender) => move |dropdown| {
+                            send!(sender, ImportDialogMsg::SetSource(dropdown.selected() as usize));
+                        }
+                    },
+                    attach(0, 1, 1, 1) = &gtk4::Label {
+                        set_label: "Into",
+                        set_halign: gtk4::Align::Start,
+                    },
+                    attach(1, 1, 1, 1): target_dropdown = &gtk4::DropDown {
+                        set_hexpand: true,
+                        set_sensitive: watch!(model.source != 0),
+                        connect_selected_notify(sender) => move |dropdown| {
+                            send!(sender, ImportDialogMsg::SetTarget(dropdown.selected() as usize));
+                        }
+                    },
+                },
+                append = &gtk4::ScrolledWindow {
+                    add_css_class: "frame",
+                    set_vexpand: true,
+                    set_hscrollbar_policy: gtk4::PolicyType::Never,
+                    set_child = Some(&gtk4::Label) {
+                        set_margin_start: 6,
+                        set_margin_end: 6,
+                        set_margin_top: 6,
+                        set_margin_bottom: 6,
+                        set_xalign: 0.,
+                        set_yalign: 0.,
+                        set_wrap: true,
+                        set_selectable: true,
+                        set_label: watch!(&model.preview()),
+                    },
+                },
+                append = &gtk4::Box {
+                    set_orientation: gtk4::Orientation::Horizontal,
+                    set_halign: gtk4::Align::End,
+                    set_spacing: 6,
+                    append = &gtk4::Button {
+                        set_label: "Cancel",
+                        connect_clicked(sender) => move |_| {
+                            send!(sender, ImportDialogMsg::Close);
+                        }
+                    },
+                    append = &gtk4::Button {
+                        add_css_class: "suggested-action",
+                        set_label: "Import",
+                        connect_clicked(sender) => move |_| {
+                            send!(sender, ImportDialogMsg::Import);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn post_view() {
+        if !model.reset {
+            return;
+        }
+        let (current, imported) = match (&model.current, &model.imported) {
+            (Some(current), Some(imported)) => (current, imported),
+            _ => return,
+        };
+
+        let mut sources = vec!["All Configurations".to_string()];
+        sources.extend(imported.profile_labels());
+        let sources: Vec<&str> = sources.iter().map(String::as_str).collect();
+        self.source_dropdown
+            .set_model(Some(&gtk4::StringList::new(&sources)));
+        self.source_dropdown.set_selected(model.source as u32);
+
+        let mut targets = current.profile_labels();
+        targets.push("New Configuration".to_string());
+        let targets: Vec<&str> = targets.iter().map(String::as_str).collect();
+        self.target_dropdown
+            .set_model(Some(&gtk4::StringList::new(&targets)));
+        self.target_dropdown.set_selected(model.target as u32);
+    }
+}
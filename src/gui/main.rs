@@ -1,39 +1,68 @@
-use gtk4::{gdk, gdk_pixbuf, gio, glib, pango, prelude::*};
+use gtk4::{gdk, gdk_pixbuf, gio, glib, glib::ToVariant, pango, prelude::*};
 use relm4::{
     actions::{RelmAction, RelmActionGroup},
     send, view, AppUpdate, Model, RelmApp, RelmComponent, RelmWorker, Sender, Widgets,
 };
-use std::{collections::HashMap, env, path::PathBuf, process::Command};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    env, fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+    thread,
+};
 
-use mouse_configurator::Event;
+use mouse_configurator::{
+    battery_history,
+    bindings::HardwareButton,
+    config::{
+        self, load_config, load_device_config, round_dpi, serialize_config, AppRule, Binding,
+        MouseConfig, Profile, DPI_STEP,
+    },
+    policy::{policy, policy_error},
+    profile_library::{self, ProfileFile},
+    Event,
+};
 
-mod bindings;
-use bindings::HardwareButton;
+mod battery;
 mod binding_dialog;
 use binding_dialog::{BindingDialogModel, BindingDialogMsg};
 mod buttons_widget;
 use buttons_widget::{ButtonsWidget, BUTTONS, IMAGE_WIDTH};
+mod dbus;
+use dbus::{DBusCall, DBusRequest};
 mod device_monitor_process;
-use device_monitor_process::DeviceMonitorProcess;
+use device_monitor_process::{open_device_monitor, DeviceMonitor};
 mod dialogs;
 use dialogs::*;
-mod keycode;
+mod focus;
+mod import_dialog;
+use import_dialog::{ImportDialogModel, ImportDialogMsg, ImportSelection};
 mod profile;
-use profile::{
-    apply_profile_diff, load_config, save_config, Binding, MouseConfig, MouseState, Profile,
-};
+use profile::{apply_profile_diff, bindings_match, effective_profile, MouseState};
+mod rules_dialog;
+use rules_dialog::{RulesDialogModel, RulesDialogMsg};
+mod shortcut;
 mod swap_button_dialog;
 use swap_button_dialog::{SwapButtonDialogModel, SwapButtonDialogMsg};
 mod util;
 mod worker;
-use worker::{DeviceId, WorkerModel, WorkerMsg};
+use worker::{DeviceError, DeviceId, DeviceOperation, WorkerModel, WorkerMsg};
 
-const DPI_STEP: f64 = 50.;
+const APP_ID: &str = "org.pop_os.mouseconfigurator";
+// Times to reapply settings that fail verification, before showing an error
+const MAX_VERIFY_ATTEMPTS: u8 = 3;
+// Seconds between battery queries
+const BATTERY_QUERY_INTERVAL: u32 = 5 * 60;
+// Seconds to wait for the mouse to reply when reading back settings
+const VERIFY_TIMEOUT: u32 = 5;
 
 #[derive(relm4::Components)]
 struct AppComponents {
     dialog: RelmComponent<BindingDialogModel, AppModel>,
     swap_button_dialog: RelmComponent<SwapButtonDialogModel, AppModel>,
+    rules_dialog: RelmComponent<RulesDialogModel, AppModel>,
+    import_dialog: RelmComponent<ImportDialogModel, AppModel>,
     worker: RelmWorker<WorkerModel, AppModel>,
 }
 
@@ -42,11 +71,16 @@ struct Device {
     state: MouseState,
     config: MouseConfig,
     serial: String,
+    // Last failed operation, until retried or dismissed
+    error: Option<DeviceError>,
+    // Contents of the config file when last loaded or saved
+    saved_config: Option<String>,
 }
 
 impl Device {
-    fn round_dpi(&self, dpi: f64) -> u16 {
-        ((dpi / DPI_STEP).round() * DPI_STEP) as u16
+    // Selected profile, as applied to the mouse
+    fn profile(&self) -> Cow<'_, Profile> {
+        effective_profile(self.config.profile(), &self.state)
     }
 
     fn apply_profile_diff(
@@ -54,18 +88,73 @@ impl Device {
         device_id: DeviceId,
         worker: &RelmWorker<WorkerModel, AppModel>,
     ) {
-        apply_profile_diff(device_id, &self.config, &mut self.state, worker);
+        let profile = self.profile().into_owned();
+        apply_profile_diff(device_id, &profile, &mut self.state, worker);
     }
 
-    fn apply_dpi_diff(&mut self, device_id: DeviceId, worker: &RelmWorker<WorkerModel, AppModel>) {
-        if let Some(state_dpi) = self.state.dpi {
-            let new = self.round_dpi(self.config.dpi);
-            let old = self.round_dpi(state_dpi);
-            if old != new {
-                // XXX don't queue infinitely?
-                send!(worker, WorkerMsg::SetDpi(device_id, new));
-            }
+    // Reapply settings if verification found they didn't match, giving up
+    // after `MAX_VERIFY_ATTEMPTS`
+    fn resync(&mut self, device_id: DeviceId, worker: &RelmWorker<WorkerModel, AppModel>) {
+        if !self.state.out_of_sync {
+            self.state.verify_attempts = 0;
+        } else if self.state.verify_attempts < MAX_VERIFY_ATTEMPTS {
+            self.state.verify_attempts += 1;
+            eprintln!("Settings on {} don't match; reapplying", self.serial);
+            self.apply_profile_diff(device_id, worker);
+        } else {
+            self.error = Some(DeviceError {
+                operation: DeviceOperation::Verify,
+                message: "the mouse didn't accept the configuration".to_string(),
+            });
+        }
+    }
+
+    fn set_dpi(
+        &mut self,
+        dpi: f64,
+        worker: &RelmWorker<WorkerModel, AppModel>,
+    ) -> Result<(), String> {
+        policy().check_dpi(dpi)?;
+        self.config.profile_mut().dpi = dpi;
+        if let Some(device_id) = self.id.clone() {
+            self.apply_profile_diff(device_id, worker);
+        }
+        Ok(())
+    }
+
+    fn set_binding(
+        &mut self,
+        button: HardwareButton,
+        binding: Binding,
+        worker: &RelmWorker<WorkerModel, AppModel>,
+    ) -> Result<(), String> {
+        policy().check_binding(button, &binding)?;
+        self.state.mouse_bindings.remove(&button);
+        if binding == Binding::Preset(button.def_binding().id) {
+            self.config.profile_mut().bindings.remove(&button);
+        } else {
+            self.config.profile_mut().bindings.insert(button, binding);
+        }
+        if let Some(device_id) = self.id.clone() {
+            self.apply_profile_diff(device_id, worker);
+        }
+        Ok(())
+    }
+
+    // Returns `true` if the profile changed
+    fn select_profile(
+        &mut self,
+        profile: usize,
+        worker: &RelmWorker<WorkerModel, AppModel>,
+    ) -> bool {
+        if profile == self.config.profile_num() || profile >= self.config.profiles().len() {
+            return false;
+        }
+        self.config.select_profile(profile);
+        if let Some(device_id) = self.id.clone() {
+            self.apply_profile_diff(device_id, worker);
         }
+        true
     }
 }
 
@@ -80,26 +169,80 @@ struct AppModel {
     profiles_changed: bool,
     show_about_mouse: bool,
     rename_config: bool,
-    device_monitor: Option<DeviceMonitorProcess>,
+    device_monitor: Option<DeviceMonitor>,
+    // Set if devices can't be accessed, like if authentication failed
+    device_monitor_error: Option<String>,
+    // Set if the device monitor exited after starting, so new devices aren't seen
+    device_monitor_stopped: Option<String>,
+    // Set while `pkexec` is started again, in another thread
+    device_monitor_retrying: bool,
     error: Option<String>,
+    // Failure to load or save the config, shown until dismissed
+    config_error: Option<String>,
+    // Set if saved configs couldn't be loaded, and must not be overwritten
+    config_read_only: bool,
+    // Devices with a config file that couldn't be loaded
+    read_only_configs: HashSet<String>,
+    // Profiles in the library and templates for the selected device's
+    // model, with labels
+    library: Vec<(String, ProfileFile)>,
+    // Identifiers of the focused application, for matching rules
+    focused_app: Vec<String>,
+    // Session bus connection, unless another instance is running
+    dbus_connection: Option<gio::DBusConnection>,
 }
 
 impl AppModel {
-    fn new(device_monitor: Option<DeviceMonitorProcess>) -> Self {
-        let devices: Vec<_> = load_config()
+    fn new(
+        device_monitor: Option<io::Result<DeviceMonitor>>,
+        dbus_connection: Option<gio::DBusConnection>,
+    ) -> Self {
+        let (device_monitor, device_monitor_error) = match device_monitor {
+            Some(Ok(device_monitor)) => (Some(device_monitor), None),
+            Some(Err(err)) => (None, Some(err.to_string())),
+            None => (None, None),
+        };
+        let (configs, config_errors) = load_config();
+        let devices: Vec<_> = configs
             .into_iter()
             .map(|(serial, config)| Device {
                 id: None,
                 state: MouseState::default(),
+                saved_config: Some(serialize_config(&config)),
                 config,
                 serial,
+                error: None,
             })
             .collect();
+        let mut config_read_only = false;
+        let mut read_only_configs = HashSet::new();
+        for err in config_errors.iter().filter(|x| x.read_only) {
+            match &err.serial {
+                Some(serial) => {
+                    read_only_configs.insert(serial.clone());
+                }
+                None => config_read_only = true,
+            }
+        }
+        let mut messages: Vec<_> = config_errors.iter().map(|x| x.to_string()).collect();
+        if let Some(err) = policy_error() {
+            messages.push(format!("{}. Settings are locked.", err));
+        }
+        let config_error = if messages.is_empty() {
+            None
+        } else {
+            Some(messages.join("\n"))
+        };
         let selected_device = if devices.len() == 1 { Some(0) } else { None };
         AppModel {
             devices,
             selected_device,
             device_monitor,
+            device_monitor_error,
+            config_error,
+            config_read_only,
+            read_only_configs,
+            dbus_connection,
             ..Default::default()
         }
     }
@@ -138,6 +281,8 @@ impl AppModel {
                 state: MouseState::default(),
                 config: MouseConfig::new(device),
                 serial,
+                error: None,
+                saved_config: None,
             };
             device.state.set_connected();
             device.state.firmware_version = Some(version);
@@ -158,12 +303,21 @@ impl AppModel {
             if device.id.as_ref() == Some(id) {
                 device.state.set_disconnected();
                 device.id = None;
+                device.error = None;
             }
         }
     }
 
     fn remove_device(&mut self, device: usize) {
-        self.devices.remove(device);
+        let device_info = self.devices.remove(device);
+        // Keep read-only configs, as `save_configs` does. The serial stays in
+        // `read_only_configs`, so the file isn't replaced if the device is
+        // connected again.
+        if !self.config_read_only && !self.read_only_configs.contains(&device_info.serial) {
+            if let Err(err) = config::remove_device_config(&device_info.serial) {
+                self.config_error = Some(err);
+            }
+        }
 
         self.device_by_id = self
             .device_by_id
@@ -190,12 +344,97 @@ impl AppModel {
         self.device_list_changed = true;
     }
 
+    // Save configs that changed since they were last loaded or saved
+    fn save_configs(&mut self) {
+        if self.config_read_only {
+            return;
+        }
+        for device in &mut self.devices {
+            if self.read_only_configs.contains(&device.serial) {
+                continue;
+            }
+            let data = serialize_config(&device.config);
+            if device.saved_config.as_ref() == Some(&data) {
+                continue;
+            }
+            match config::write_config_data(&device.serial, &data) {
+                Ok(()) => device.saved_config = Some(data),
+                Err(err) => self.config_error = Some(err),
+            }
+        }
+    }
+
+    // Load a config file that was changed outside the app
+    fn reload_config(&mut self, serial: String, worker: &RelmWorker<WorkerModel, AppModel>) {
+        let idx = self.devices.iter().position(|x| x.serial == serial);
+        let config = match load_device_config(&serial) {
+            Ok(config) => config,
+            Err(err) => {
+                if err.read_only {
+                    self.read_only_configs.insert(serial);
+                }
+                self.config_error = Some(err.to_string());
+                return;
+            }
+        };
+        if self.read_only_configs.remove(&serial) {
+            self.config_error = None;
+        }
+
+        let mut config = match (config, idx) {
+            (Some(config), _) => config,
+            // Forget a removed config, unless the device is connected
+            (None, Some(idx)) if self.devices[idx].id.is_none() => {
+                self.remove_device(idx);
+                return;
+            }
+            (None, Some(idx)) => {
+                self.devices[idx].saved_config = None;
+                return;
+            }
+            (None, None) => return,
+        };
+        let data = serialize_config(&config);
+        config.apply_rules(&self.focused_app);
+
+        let idx = match idx {
+            Some(idx) => idx,
+            None => {
+                self.devices.push(Device {
+                    id: None,
+                    state: MouseState::default(),
+                    config,
+                    serial,
+                    error: None,
+                    saved_config: Some(data),
+                });
+                self.device_list_changed = true;
+                return;
+            }
+        };
+        let device = &mut self.devices[idx];
+        // Written by `save_configs`
+        if device.saved_config.as_ref() == Some(&data) {
+            return;
+        }
+        device.config = config;
+        device.saved_config = Some(data);
+        if let Some(device_id) = device.id.clone() {
+            device.apply_profile_diff(device_id, worker);
+        }
+        if self.selected_device == Some(idx) {
+            self.profiles_changed = true;
+            self.bindings_changed = true;
+        }
+        self.device_list_changed = true;
+    }
+
     // Swap left and right buttons, if in left handed mode
     fn swap_buttons(&self, button: Option<HardwareButton>) -> Option<HardwareButton> {
         if let Some(device) = self.device() {
-            if device.config.profile().left_handed && button.is_none() {
+            if device.profile().left_handed && button.is_none() {
                 Some(HardwareButton::Right)
-            } else if device.config.profile().left_handed && button == Some(HardwareButton::Right) {
+            } else if device.profile().left_handed && button == Some(HardwareButton::Right) {
                 None
             } else {
                 button
@@ -209,13 +448,134 @@ impl AppModel {
         self.selected_device = selected_device.filter(|x| *x < self.devices.len());
         self.bindings_changed = true;
         self.profiles_changed = true;
+        self.load_library();
+    }
+
+    fn load_library(&mut self) {
+        let model = match self.device() {
+            Some(device) => device.config.device.clone(),
+            None => return,
+        };
+        let library = profile_library::list(&model).unwrap_or_else(|err| {
+            eprintln!("Failed to load profile library: {}", err);
+            Vec::new()
+        });
+        let library = library
+            .into_iter()
+            .map(|(path, file)| (format!("{} (Library)", file.label(&path)), file));
+        let templates = profile_library::templates(&model)
+            .into_iter()
+            .map(|(path, file)| (format!("{} (Template)", file.label(&path)), file));
+        self.library = library.chain(templates).collect();
+    }
+
+    fn device_by_serial_mut(&mut self, serial: &str) -> Result<(usize, &mut Device), String> {
+        self.devices
+            .iter_mut()
+            .enumerate()
+            .find(|(_, device)| device.serial == serial)
+            .ok_or_else(|| format!("No device with serial `{}`", serial))
+    }
+
+    fn handle_dbus_request(
+        &mut self,
+        request: DBusRequest,
+        worker: &RelmWorker<WorkerModel, AppModel>,
+    ) -> Result<glib::Variant, String> {
+        match request {
+            DBusRequest::ListDevices => {
+                let devices: Vec<HashMap<String, glib::Variant>> = self
+                    .devices
+                    .iter()
+                    .map(|device| {
+                        let mut info = HashMap::new();
+                        info.insert("serial".to_string(), device.serial.to_variant());
+                        info.insert("model".to_string(), device.config.device.to_variant());
+                        info.insert("connected".to_string(), device.state.connected.to_variant());
+                        info.insert(
+                            "profile".to_string(),
+                            (device.config.profile_num() as u32).to_variant(),
+                        );
+                        info.insert(
+                            "dpi".to_string(),
+                            u32::from(round_dpi(device.profile().dpi)).to_variant(),
+                        );
+                        if let Some(level) = device.state.battery_percent {
+                            info.insert("battery".to_string(), level.to_variant());
+                        }
+                        if let Some((major, minor, patch)) = device.state.firmware_version {
+                            info.insert(
+                                "firmware".to_string(),
+                                format!("{}.{}.{}", major, minor, patch).to_variant(),
+                            );
+                        }
+                        info
+                    })
+                    .collect();
+                Ok((devices,).to_variant())
+            }
+            DBusRequest::GetProfile(serial) => {
+                let (_, device) = self.device_by_serial_mut(&serial)?;
+                Ok((device.config.profile_num() as u32,).to_variant())
+            }
+            DBusRequest::SetProfile(serial, profile) => {
+                let (idx, device) = self.device_by_serial_mut(&serial)?;
+                if profile as usize >= device.config.profiles().len() {
+                    return Err(format!("Invalid profile {}", profile));
+                }
+                if device.select_profile(profile as usize, worker)
+                    && self.selected_device == Some(idx)
+                {
+                    self.profiles_changed = true;
+                    self.bindings_changed = true;
+                }
+                Ok(().to_variant())
+            }
+            DBusRequest::GetDpi(serial) => {
+                let (_, device) = self.device_by_serial_mut(&serial)?;
+                Ok((u32::from(round_dpi(device.profile().dpi)),).to_variant())
+            }
+            DBusRequest::SetDpi(serial, dpi) => {
+                let (_, device) = self.device_by_serial_mut(&serial)?;
+                device.set_dpi(dpi.into(), worker)?;
+                Ok(().to_variant())
+            }
+            DBusRequest::SetBinding(serial, button, binding) => {
+                let button = u8::try_from(button)
+                    .ok()
+                    .and_then(HardwareButton::from_u8)
+                    .ok_or_else(|| format!("Invalid button {}", button))?;
+                let binding = if binding == "default" {
+                    Binding::Preset(button.def_binding().id)
+                } else {
+                    Binding::parse(&binding)?
+                };
+                let (idx, device) = self.device_by_serial_mut(&serial)?;
+                device.set_binding(button, binding, worker)?;
+                if self.selected_device == Some(idx) {
+                    self.bindings_changed = true;
+                }
+                Ok(().to_variant())
+            }
+        }
     }
 }
 
 enum AppMsg {
     SetDeviceMonitor,
+    RetryDeviceMonitor,
+    DeviceMonitorOpened(io::Result<DeviceMonitor>),
+    DeviceMonitorStopped(String),
+    ShowError(String),
     DeviceAdded(DeviceId),
     DeviceRemoved(DeviceId),
+    DeviceError(DeviceId, DeviceError),
+    VerifyStarted(DeviceId),
+    // Verify request, by number, that may not have been answered
+    VerifyTimedOut(DeviceId, u64),
+    RetryDeviceOperation,
+    DismissDeviceError,
+    QueryBattery,
     ToggleRenameConfig,
     RenameConfig(Option<String>),
     Event(DeviceId, Event),
@@ -227,10 +587,25 @@ enum AppMsg {
     Remove,
     SelectDevice(Option<usize>),
     SaveConfig,
+    // Config file for the device with this serial number was changed
+    ConfigFileChanged(String),
+    DismissConfigError,
     ShowAboutMouse,
     SelectProfile(usize),
+    AddProfile,
+    DuplicateProfile,
+    RemoveProfile,
+    // Move the active profile up or down by one
+    MoveProfile(bool),
     ExportConfig(PathBuf),
     ImportConfig(PathBuf),
+    ExportProfile(PathBuf),
+    SaveProfileToLibrary,
+    ApplyImport(ImportSelection),
+    ShowRules,
+    SetRules(Vec<AppRule>),
+    FocusChanged(Vec<String>),
+    DBusCall(DBusCall),
 }
 
 impl Model for AppModel {
@@ -240,7 +615,7 @@ impl Model for AppModel {
 }
 
 impl AppUpdate for AppModel {
-    fn update(&mut self, msg: AppMsg, components: &AppComponents, _sender: Sender<AppMsg>) -> bool {
+    fn update(&mut self, msg: AppMsg, components: &AppComponents, sender: Sender<AppMsg>) -> bool {
         self.bindings_changed = false;
         self.device_list_changed = false;
         self.show_about_mouse = false;
@@ -265,40 +640,190 @@ impl AppUpdate for AppModel {
                     );
                 }
             }
+            AppMsg::RetryDeviceMonitor => {
+                // Waits for `pkexec`, which shows an authentication dialog
+                if !self.device_monitor_retrying {
+                    self.device_monitor_retrying = true;
+                    thread::spawn(move || {
+                        send!(sender, AppMsg::DeviceMonitorOpened(open_device_monitor()));
+                    });
+                }
+            }
+            AppMsg::DeviceMonitorOpened(res) => match res {
+                Ok(device_monitor) => {
+                    self.device_monitor_retrying = false;
+                    self.device_monitor_error = None;
+                    self.device_monitor_stopped = None;
+                    send!(
+                        components.worker,
+                        WorkerMsg::SetDeviceMonitor(device_monitor)
+                    );
+                }
+                // Keep showing devices that are already open
+                Err(err) if self.device_monitor_stopped.is_some() => {
+                    self.device_monitor_retrying = false;
+                    self.device_monitor_stopped = Some(err.to_string());
+                }
+                Err(err) => {
+                    self.device_monitor_retrying = false;
+                    self.device_monitor_error = Some(err.to_string());
+                }
+            },
+            AppMsg::DeviceMonitorStopped(reason) => {
+                self.device_monitor_stopped = Some(reason);
+            }
+            AppMsg::ShowError(error) => {
+                self.error = Some(error);
+            }
             AppMsg::DeviceAdded(_id) => {
                 // Do nothing until we get `Event::Firmware`
             }
             AppMsg::DeviceRemoved(id) => {
+                if let Some(device) = self.device_by_id_mut(&id) {
+                    let serial = device.serial.clone();
+                    dbus::emit_device_disconnected(self.dbus_connection.as_ref(), &serial);
+                }
                 self.remove_device_id(&id);
             }
+            AppMsg::DeviceError(id, error) => {
+                eprintln!("{}", error);
+                if let Some(device) = self.device_by_id_mut(&id) {
+                    if let DeviceOperation::Verify = error.operation {
+                        device.state.verify_pending = false;
+                    }
+                    device.error = Some(error);
+                }
+            }
+            AppMsg::VerifyStarted(id) => {
+                if let Some(device) = self.device_by_id_mut(&id) {
+                    device.state.verify_pending = true;
+                    device.state.verify_requests += 1;
+                    let request = device.state.verify_requests;
+                    // Otherwise an unrelated report would be taken as the reply
+                    glib::timeout_add_seconds_local_once(VERIFY_TIMEOUT, move || {
+                        send!(sender, AppMsg::VerifyTimedOut(id, request));
+                    });
+                }
+            }
+            AppMsg::VerifyTimedOut(id, request) => {
+                if let Some(device) = self.device_by_id_mut(&id) {
+                    if device.state.verify_pending && device.state.verify_requests == request {
+                        eprintln!("No reply verifying settings on {}", device.serial);
+                        device.state.verify_pending = false;
+                    }
+                }
+            }
+            AppMsg::RetryDeviceOperation => {
+                if let Some(device) = self.device_mut() {
+                    if let (Some(id), Some(error)) = (device.id.clone(), device.error.take()) {
+                        if let DeviceOperation::Verify = error.operation {
+                            device.state.verify_attempts = 0;
+                        }
+                        send!(components.worker, WorkerMsg::Run(id, error.operation));
+                    }
+                }
+            }
+            AppMsg::QueryBattery => {
+                for id in self.device_by_id.keys() {
+                    send!(
+                        components.worker,
+                        WorkerMsg::Run(id.clone(), DeviceOperation::QueryBattery)
+                    );
+                }
+            }
+            AppMsg::DismissDeviceError => {
+                if let Some(device) = self.device_mut() {
+                    device.error = None;
+                }
+            }
+            // Reports from a device before `Event::Firmware` are ignored, since
+            // its settings are queried once it is identified
             AppMsg::Event(device_id, event) => match event {
-                Event::Battery { level, .. } => {
-                    let device = self.device_by_id_mut(&device_id).unwrap();
-                    device.state.battery_percent = Some(level);
+                // Reply to a query, or reported by the device periodically
+                Event::Battery {
+                    level,
+                    low_level,
+                    crit_level,
+                    ..
+                } => {
+                    if let Some(device) = self.device_by_id_mut(&device_id) {
+                        let previous = device.state.battery_percent;
+                        if previous != Some(level) {
+                            device.state.battery_percent = Some(level);
+                            let serial = device.serial.clone();
+                            battery::notify_level_change(
+                                &serial, previous, level, low_level, crit_level,
+                            );
+                            if let Err(err) = battery_history::record(&serial, level) {
+                                eprintln!("Failed to record battery level: {}", err);
+                            }
+                            dbus::emit_battery_changed(
+                                self.dbus_connection.as_ref(),
+                                &serial,
+                                level,
+                            );
+                            self.device_list_changed = true;
+                        }
+                    }
                 }
                 Event::Mouse {
                     dpi, left_handed, ..
                 } => {
-                    let device = self.device_by_id_mut(&device_id).unwrap();
-
-                    // Sync dpi from config
-                    if device.state.dpi.is_none() {
-                        device.state.dpi = Some(dpi.into());
-                        device.apply_dpi_diff(device_id.clone(), &components.worker);
-                    }
-
-                    // Sync left_handed from config
-                    if device.state.left_handed.is_none() {
-                        device.state.left_handed = Some(left_handed);
-                        device.apply_profile_diff(device_id.clone(), &components.worker);
+                    if let Some(device) = self.device_by_id_mut(&device_id) {
+                        if device.state.verify_pending {
+                            // Last reply to `DeviceOperation::Verify`
+                            device.state.verify_pending = false;
+                            if dpi != round_dpi(device.profile().dpi)
+                                || left_handed != device.profile().left_handed
+                            {
+                                device.state.out_of_sync = true;
+                            }
+                            device.state.dpi = Some(dpi.into());
+                            device.state.left_handed = Some(left_handed);
+                            device.resync(device_id, &components.worker);
+                        } else {
+                            // Sync dpi and left_handed from config
+                            if device.state.dpi.is_none() || device.state.left_handed.is_none() {
+                                device.state.dpi = Some(dpi.into());
+                                device.state.left_handed = Some(left_handed);
+                                device.apply_profile_diff(device_id.clone(), &components.worker);
+                            }
+                        }
                     }
                 }
                 Event::Buttons {
                     buttons, host_id, ..
                 } => {
-                    let device = self.device_by_id_mut(&device_id).unwrap();
-                    if device.state.bindings.is_none() {
-                        device.state.set_bindings_from_buttons(host_id, &buttons);
+                    if let Some(device) = self.device_by_id_mut(&device_id) {
+                        if device.state.verify_pending {
+                            let mut read = MouseState::default();
+                            read.set_bindings_from_buttons(host_id, &buttons);
+                            let bindings = read.bindings.unwrap_or_default();
+                            device.state.out_of_sync =
+                                !bindings_match(&device.profile(), &bindings);
+                            device.state.bindings = Some(bindings);
+                        } else if device.state.bindings.is_none() {
+                            device.state.set_bindings_from_buttons(host_id, &buttons);
+                            // Keep bindings set by other programs, until imported
+                            // or replaced in the binding dialog
+                            let profile = device.config.profile();
+                            let mouse_bindings = device
+                                .state
+                                .bindings
+                                .iter()
+                                .flatten()
+                                .filter(|(id, binding)| {
+                                    binding.is_custom()
+                                        && !profile.bindings.contains_key(id)
+                                        && policy().check_binding(**id, binding).is_ok()
+                                })
+                                .map(|(id, binding)| (*id, binding.clone()))
+                                .collect();
+                            device.state.mouse_bindings = mouse_bindings;
+                            if !device.state.mouse_bindings.is_empty() {
+                                self.bindings_changed = true;
+                            }
+                        }
                     }
                 }
                 Event::Firmware {
@@ -306,26 +831,27 @@ impl AppUpdate for AppModel {
                     serial,
                     version,
                 } => {
+                    dbus::emit_device_connected(self.dbus_connection.as_ref(), &serial);
                     self.add_or_update_device(device_id, device, serial, version);
                 }
                 _ => {}
             },
             AppMsg::SetDpi(value) => {
                 if let Some(device) = self.device_mut() {
-                    device.config.dpi = value;
-                    if let Some(device_id) = device.id.clone() {
-                        device.apply_dpi_diff(device_id, &components.worker);
+                    if let Err(err) = device.set_dpi(value, &components.worker) {
+                        self.error = Some(err);
                     }
                 }
             }
             AppMsg::SelectButton(button) => {
                 let button = self.swap_buttons(button);
                 if let Some(id) = button {
-                    send!(components.dialog, BindingDialogMsg::Show(id))
-                } else {
-                    let left_handed = self
+                    let mouse_binding = self
                         .device()
-                        .map_or(false, |x| x.config.profile().left_handed);
+                        .and_then(|x| x.state.mouse_bindings.get(&id).cloned());
+                    send!(components.dialog, BindingDialogMsg::Show(id, mouse_binding))
+                } else {
+                    let left_handed = self.device().map_or(false, |x| x.profile().left_handed);
                     send!(
                         components.swap_button_dialog,
                         SwapButtonDialogMsg::Show(left_handed)
@@ -334,19 +860,16 @@ impl AppUpdate for AppModel {
             }
             AppMsg::SetBinding(button, binding) => {
                 if let Some(device) = self.device_mut() {
-                    if binding == Binding::Preset(button.def_binding().id) {
-                        device.config.profile_mut().bindings.remove(&button);
-                    } else {
-                        device.config.profile_mut().bindings.insert(button, binding);
-                    }
-                    if let Some(device_id) = device.id.clone() {
-                        device.apply_profile_diff(device_id, &components.worker);
+                    if let Err(err) = device.set_binding(button, binding, &components.worker) {
+                        self.error = Some(err);
                     }
                     self.bindings_changed = true;
                 }
             }
             AppMsg::SetLeftHanded(left_handed) => {
-                if let Some(device) = self.device_mut() {
+                if let Err(err) = policy().check_left_handed(left_handed) {
+                    self.error = Some(err);
+                } else if let Some(device) = self.device_mut() {
                     device.config.profile_mut().left_handed = left_handed;
                     if let Some(device_id) = device.id.clone() {
                         device.apply_profile_diff(device_id, &components.worker);
@@ -356,16 +879,13 @@ impl AppUpdate for AppModel {
             }
             AppMsg::Reset => {
                 if let Some(device) = self.device_mut() {
-                    for profile in device.config.profiles_mut() {
-                        *profile = Profile::default();
-                    }
-                    device.config.dpi = 1200.; // XXX depend on device
+                    device.config.reset_profiles();
 
                     if let Some(device_id) = device.id.clone() {
-                        device.apply_profile_diff(device_id.clone(), &components.worker);
-                        device.apply_dpi_diff(device_id, &components.worker);
+                        device.apply_profile_diff(device_id, &components.worker);
                     }
 
+                    self.profiles_changed = true;
                     self.bindings_changed = true;
                 }
             }
@@ -380,17 +900,88 @@ impl AppUpdate for AppModel {
                 }
             }
             AppMsg::SaveConfig => {
-                save_config(self.devices.iter().map(|x| (&x.serial, &x.config)));
+                self.save_configs();
+            }
+            AppMsg::ConfigFileChanged(serial) => {
+                self.reload_config(serial, &components.worker);
+            }
+            AppMsg::DismissConfigError => {
+                self.config_error = None;
             }
             AppMsg::ShowAboutMouse => {
                 self.show_about_mouse = true;
             }
             AppMsg::SelectProfile(profile) => {
-                if let Some(device) = self.device_mut() {
-                    if profile != device.config.profile_num()
-                        && profile < device.config.profiles().len()
+                let library = &self.library;
+                if let Some(device) = self.selected_device.map(|idx| &mut self.devices[idx]) {
+                    let num_profiles = device.config.profiles().len();
+                    // Entries after the device's profiles are from the library, or templates
+                    if let Some((_, file)) = profile
+                        .checked_sub(num_profiles)
+                        .and_then(|x| library.get(x))
                     {
-                        device.config.select_profile(profile);
+                        // Select a copy already added, rather than adding another
+                        let profile = file.profile();
+                        let profile = match device.config.find_profile(&profile) {
+                            Some(idx) => idx,
+                            None => device.config.import_profile(profile, None),
+                        };
+                        device.select_profile(profile, &components.worker);
+                        self.profiles_changed = true;
+                        self.bindings_changed = true;
+                    } else if device.select_profile(profile, &components.worker) {
+                        self.profiles_changed = true;
+                        self.bindings_changed = true;
+                    }
+                }
+            }
+            AppMsg::ExportProfile(path) => {
+                if let Some(device) = self.device() {
+                    let file =
+                        ProfileFile::new(device.config.device.clone(), device.config.profile());
+                    if let Err(err) = file.save(&path) {
+                        self.error = Some(format!("Failed to export configuration: {}", err));
+                    }
+                }
+            }
+            AppMsg::SaveProfileToLibrary => {
+                if let Some(device) = self.device() {
+                    let file =
+                        ProfileFile::new(device.config.device.clone(), device.config.profile());
+                    let label = device.config.profile_labels()[device.config.profile_num()].clone();
+                    match profile_library::add(&file, &label) {
+                        Ok(_) => {
+                            self.load_library();
+                            self.profiles_changed = true;
+                        }
+                        Err(err) => {
+                            self.error = Some(format!("Failed to save to library: {}", err));
+                        }
+                    }
+                }
+            }
+            AppMsg::AddProfile => {
+                if let Some(device) = self.device_mut() {
+                    let profile = device.config.add_profile();
+                    device.select_profile(profile, &components.worker);
+                    self.profiles_changed = true;
+                    self.bindings_changed = true;
+                }
+            }
+            AppMsg::DuplicateProfile => {
+                if let Some(device) = self.device_mut() {
+                    let profile = device.config.profile_num();
+                    if let Some(copy) = device.config.duplicate_profile(profile) {
+                        device.select_profile(copy, &components.worker);
+                        self.profiles_changed = true;
+                        self.bindings_changed = true;
+                    }
+                }
+            }
+            AppMsg::RemoveProfile => {
+                if let Some(device) = self.device_mut() {
+                    let profile = device.config.profile_num();
+                    if device.config.remove_profile(profile) {
                         if let Some(device_id) = device.id.clone() {
                             device.apply_profile_diff(device_id, &components.worker);
                         }
@@ -399,11 +990,83 @@ impl AppUpdate for AppModel {
                     }
                 }
             }
-            AppMsg::ImportConfig(path) => {
+            AppMsg::MoveProfile(up) => {
                 if let Some(device) = self.device_mut() {
-                    match MouseConfig::import(&path) {
+                    let from = device.config.profile_num();
+                    let to = if up {
+                        from.checked_sub(1)
+                    } else {
+                        Some(from + 1)
+                    };
+                    if let Some(to) = to {
+                        // Both the manual and rule selection follow the
+                        // moved profile
+                        if device.config.move_profile(from, to) {
+                            self.profiles_changed = true;
+                        }
+                    }
+                }
+            }
+            AppMsg::ShowRules => {
+                if let Some(device) = self.device() {
+                    send!(
+                        components.rules_dialog,
+                        RulesDialogMsg::Show(
+                            device.config.rules.clone(),
+                            device.config.profile_labels()
+                        )
+                    );
+                }
+            }
+            AppMsg::SetRules(rules) => {
+                let focused_app = &self.focused_app;
+                if let Some(device) = self.selected_device.map(|idx| &mut self.devices[idx]) {
+                    device.config.rules = rules;
+                    if device.config.apply_rules(focused_app) {
+                        if let Some(device_id) = device.id.clone() {
+                            device.apply_profile_diff(device_id, &components.worker);
+                        }
+                        self.profiles_changed = true;
+                        self.bindings_changed = true;
+                    }
+                }
+            }
+            AppMsg::FocusChanged(app_ids) => {
+                // Keep the current profile while configuring it
+                if app_ids.iter().any(|x| x == APP_ID) {
+                    return true;
+                }
+                self.focused_app = app_ids;
+                for (idx, device) in self.devices.iter_mut().enumerate() {
+                    if device.config.apply_rules(&self.focused_app) {
+                        if let Some(device_id) = device.id.clone() {
+                            device.apply_profile_diff(device_id, &components.worker);
+                        }
+                        if self.selected_device == Some(idx) {
+                            self.profiles_changed = true;
+                            self.bindings_changed = true;
+                        }
+                    }
+                }
+            }
+            AppMsg::DBusCall(call) => {
+                call.handle(|request| self.handle_dbus_request(request, &components.worker));
+            }
+            AppMsg::ImportConfig(path) => {
+                if let Some(device) = self.device() {
+                    let res = import_file(&path).and_then(|config| {
+                        device.config.check_model(&config)?;
+                        Ok(config)
+                    });
+                    match res {
                         Ok(config) => {
-                            device.config = config;
+                            send!(
+                                components.import_dialog,
+                                ImportDialogMsg::Show(
+                                    Box::new(device.config.clone()),
+                                    Box::new(config)
+                                )
+                            );
                         }
                         Err(err) => {
                             self.error = Some(format!("Failed to import config: {}", err));
@@ -411,6 +1074,26 @@ impl AppUpdate for AppModel {
                     }
                 }
             }
+            AppMsg::ApplyImport(selection) => {
+                let focused_app = &self.focused_app;
+                if let Some(device) = self.selected_device.map(|idx| &mut self.devices[idx]) {
+                    match selection {
+                        ImportSelection::All(config) => {
+                            device.config = config;
+                            device.config.apply_rules(focused_app);
+                        }
+                        ImportSelection::Profile(profile, slot) => {
+                            let profile = device.config.import_profile(profile, slot);
+                            device.config.select_profile(profile);
+                        }
+                    }
+                    if let Some(device_id) = device.id.clone() {
+                        device.apply_profile_diff(device_id, &components.worker);
+                    }
+                    self.profiles_changed = true;
+                    self.bindings_changed = true;
+                }
+            }
             AppMsg::ExportConfig(path) => {
                 if let Some(device) = self.device_mut() {
                     match device.config.export(&path) {
@@ -446,175 +1129,287 @@ impl Widgets<AppModel, ()> for AppWidgets {
                     set_icon_name: "open-menu-symbolic"
                 }
             },
-            set_child = Some(&gtk4::ScrolledWindow) {
-                set_hscrollbar_policy: gtk4::PolicyType::Never,
-                set_child: stack = Some(&gtk4::Stack) {
-                    add_child: no_device_page = &gtk4::Box {
-                        set_orientation: gtk4::Orientation::Vertical,
-                        set_halign: gtk4::Align::Center,
-                        set_valign: gtk4::Align::Center,
-                        set_spacing: 6,
-                        append = &gtk4::Image {
-                            set_icon_name: Some("input-mouse-symbolic"),
-                            set_pixel_size: 128,
-                        },
-                        append = &gtk4::Label {
-                            set_label: "No Mouse Detected",
-                            set_attributes = Some(&pango::AttrList) {
-                                insert: pango::AttrInt::new_weight(pango::Weight::Bold),
-                                insert: pango::AttrFloat::new_scale(pango::SCALE_LARGE)
-                            },
-                        },
-                        append = &gtk4::Label {
-                            set_label: "If using USB connection, make sure it is plugged in properly.",
-                        },
-                        append = &gtk4::LinkButton {
-                            set_label: "Check Bluetooth Settings",
-                            connect_activate_link => |_| {
-                                let _ = Command::new("gnome-control-center").arg("bluetooth").spawn();
-                                gtk4::Inhibit(true)
-                            }
-                        }
+            set_child = Some(&gtk4::Box) {
+                set_orientation: gtk4::Orientation::Vertical,
+                append = &gtk4::InfoBar {
+                    set_message_type: gtk4::MessageType::Warning,
+                    set_revealed: watch! { model.device_monitor_stopped.is_some() },
+                    add_child = &gtk4::Label {
+                        set_hexpand: true,
+                        set_xalign: 0.,
+                        set_wrap: true,
+                        set_label: watch! { &format!("New mice won't be detected: {}", model.device_monitor_stopped.as_deref().unwrap_or("")) },
                     },
-                    add_child: device_list_page = &gtk4::ListBox {
-                        add_css_class: "frame",
-                        set_header_func: util::header_func,
-                        set_halign: gtk4::Align::Center,
+                    add_child = &gtk4::Button {
+                        set_label: "Reconnect",
                         set_valign: gtk4::Align::Center,
-                        set_margin_start: 12,
-                        set_margin_end: 12,
-                        set_margin_top: 12,
-                        set_margin_bottom: 12,
-                        connect_row_activated(sender) => move |_, row| {
-                            let idx = usize::try_from(row.index()).ok();
-                            send!(sender, AppMsg::SelectDevice(idx));
+                        set_sensitive: watch! { !model.device_monitor_retrying },
+                        connect_clicked(sender) => move |_| {
+                            send!(sender, AppMsg::RetryDeviceMonitor);
                         }
+                    }
+                },
+                append = &gtk4::InfoBar {
+                    set_message_type: gtk4::MessageType::Error,
+                    set_show_close_button: true,
+                    set_revealed: watch! { model.config_error.is_some() },
+                    add_child = &gtk4::Label {
+                        set_hexpand: true,
+                        set_xalign: 0.,
+                        set_wrap: true,
+                        set_label: watch! { model.config_error.as_deref().unwrap_or("") },
                     },
-                    add_child: device_page = &gtk4::Box {
-                        set_orientation: gtk4::Orientation::Vertical,
-                        set_spacing: 18,
-                        set_halign: gtk4::Align::Center,
-                        set_hexpand: false,
-                        set_margin_start: 12,
-                        set_margin_end: 12,
-                        set_margin_top: 18,
-                        set_margin_bottom: 36,
-                        append = &gtk4::Box {
-                            set_orientation: gtk4::Orientation::Horizontal,
+                    connect_response(sender) => move |_, response| {
+                        if response == gtk4::ResponseType::Close {
+                            send!(sender, AppMsg::DismissConfigError);
+                        }
+                    }
+                },
+                append = &gtk4::ScrolledWindow {
+                    set_vexpand: true,
+                    set_hscrollbar_policy: gtk4::PolicyType::Never,
+                    set_child: stack = Some(&gtk4::Stack) {
+                        add_child: no_device_page = &gtk4::Box {
+                            set_orientation: gtk4::Orientation::Vertical,
                             set_halign: gtk4::Align::Center,
-                            set_spacing: 12,
-                            append = &gtk4::Box {
-                                set_orientation: gtk4::Orientation::Horizontal,
-                                set_spacing: 6,
-                                set_visible: watch! { model.device().map_or(false, |x| x.state.connected) },
-                                append = &gtk4::Image {
-                                    set_from_icon_name: Some("battery-symbolic"),
+                            set_valign: gtk4::Align::Center,
+                            set_spacing: 6,
+                            append = &gtk4::Image {
+                                set_icon_name: Some("input-mouse-symbolic"),
+                                set_pixel_size: 128,
+                            },
+                            append = &gtk4::Label {
+                                set_label: "No Mouse Detected",
+                                set_attributes = Some(&pango::AttrList) {
+                                    insert: pango::AttrInt::new_weight(pango::Weight::Bold),
+                                    insert: pango::AttrFloat::new_scale(pango::SCALE_LARGE)
                                 },
-                                append = &gtk4::Label {
-                                    set_label: watch! { &format!("{}%", model.device().and_then(|x| x.state.battery_percent).unwrap_or(0)) }
+                            },
+                            append = &gtk4::Label {
+                                set_label: "If using USB connection, make sure it is plugged in properly.",
+                            },
+                            append = &gtk4::LinkButton {
+                                set_label: "Check Bluetooth Settings",
+                                connect_activate_link => |_| {
+                                    let _ = Command::new("gnome-control-center").arg("bluetooth").spawn();
+                                    gtk4::Inhibit(true)
+                                }
+                            }
+                        },
+                        add_child: no_permission_page = &gtk4::Box {
+                            set_orientation: gtk4::Orientation::Vertical,
+                            set_halign: gtk4::Align::Center,
+                            set_valign: gtk4::Align::Center,
+                            set_spacing: 6,
+                            append = &gtk4::Image {
+                                set_icon_name: Some("dialog-password-symbolic"),
+                                set_pixel_size: 128,
+                            },
+                            append = &gtk4::Label {
+                                set_label: "Unable to Access Mouse",
+                                set_attributes = Some(&pango::AttrList) {
+                                    insert: pango::AttrInt::new_weight(pango::Weight::Bold),
+                                    insert: pango::AttrFloat::new_scale(pango::SCALE_LARGE)
                                 },
                             },
+                            append = &gtk4::Label {
+                                set_label: "Administrator authentication is needed to configure the mouse, unless the Mouse Configurator udev rule is installed.",
+                                set_wrap: true,
+                                set_justify: gtk4::Justification::Center,
+                            },
+                            append = &gtk4::Label {
+                                add_css_class: "dim-label",
+                                set_label: watch! { model.device_monitor_error.as_deref().unwrap_or("") },
+                                set_wrap: true,
+                            },
                             append = &gtk4::Button {
-                                add_css_class: "flat",
-                                set_child = Some(&gtk4::Box) {
+                                set_label: "Try Again",
+                                set_halign: gtk4::Align::Center,
+                                set_sensitive: watch! { !model.device_monitor_retrying },
+                                connect_clicked(sender) => move |_| {
+                                    send!(sender, AppMsg::RetryDeviceMonitor);
+                                }
+                            }
+                        },
+                        add_child: device_list_page = &gtk4::ListBox {
+                            add_css_class: "frame",
+                            set_header_func: util::header_func,
+                            set_halign: gtk4::Align::Center,
+                            set_valign: gtk4::Align::Center,
+                            set_margin_start: 12,
+                            set_margin_end: 12,
+                            set_margin_top: 12,
+                            set_margin_bottom: 12,
+                            connect_row_activated(sender) => move |_, row| {
+                                let idx = usize::try_from(row.index()).ok();
+                                send!(sender, AppMsg::SelectDevice(idx));
+                            }
+                        },
+                        add_child: device_page = &gtk4::Box {
+                            set_orientation: gtk4::Orientation::Vertical,
+                            set_spacing: 18,
+                            set_halign: gtk4::Align::Center,
+                            set_hexpand: false,
+                            set_margin_start: 12,
+                            set_margin_end: 12,
+                            set_margin_top: 18,
+                            set_margin_bottom: 36,
+                            append = &gtk4::InfoBar {
+                                set_message_type: gtk4::MessageType::Warning,
+                                set_revealed: watch! { model.device().filter(|x| x.error.is_none()).map(|x| x.state.out_of_sync) == Some(true) },
+                                add_child = &gtk4::Label {
+                                    set_hexpand: true,
+                                    set_xalign: 0.,
+                                    set_wrap: true,
+                                    set_label: "Settings on the mouse didn't match the configuration, and are being reapplied.",
+                                },
+                            },
+                            append = &gtk4::InfoBar {
+                                set_message_type: gtk4::MessageType::Error,
+                                set_show_close_button: true,
+                                set_revealed: watch! { model.device().and_then(|x| x.error.as_ref()).is_some() },
+                                add_child = &gtk4::Label {
+                                    set_hexpand: true,
+                                    set_xalign: 0.,
+                                    set_wrap: true,
+                                    set_label: watch! { &model.device().and_then(|x| x.error.as_ref()).map_or(String::new(), |x| x.to_string()) },
+                                },
+                                add_child = &gtk4::Button {
+                                    set_label: "Retry",
+                                    set_valign: gtk4::Align::Center,
+                                    connect_clicked(sender) => move |_| {
+                                        send!(sender, AppMsg::RetryDeviceOperation);
+                                    }
+                                },
+                                connect_response(sender) => move |_, response| {
+                                    if response == gtk4::ResponseType::Close {
+                                        send!(sender, AppMsg::DismissDeviceError);
+                                    }
+                                }
+                            },
+                            append = &gtk4::Box {
+                                set_orientation: gtk4::Orientation::Horizontal,
+                                set_halign: gtk4::Align::Center,
+                                set_spacing: 12,
+                                append = &gtk4::Box {
                                     set_orientation: gtk4::Orientation::Horizontal,
                                     set_spacing: 6,
+                                    set_visible: watch! { model.device().map_or(false, |x| x.state.connected) },
                                     append = &gtk4::Image {
-                                        set_from_icon_name: Some("help-info-symbolic"),
+                                        set_from_icon_name: watch! { Some(&battery::icon_name(model.device().and_then(|x| x.state.battery_percent))) },
                                     },
                                     append = &gtk4::Label {
-                                        set_label: "About This Mouse",
-                                    }
+                                        set_label: watch! { &format!("{}%", model.device().and_then(|x| x.state.battery_percent).unwrap_or(0)) }
+                                    },
+                                },
+                                append = &gtk4::Button {
+                                    add_css_class: "flat",
+                                    set_child = Some(&gtk4::Box) {
+                                        set_orientation: gtk4::Orientation::Horizontal,
+                                        set_spacing: 6,
+                                        append = &gtk4::Image {
+                                            set_from_icon_name: Some("help-info-symbolic"),
+                                        },
+                                        append = &gtk4::Label {
+                                            set_label: "About This Mouse",
+                                        }
+                                    },
+                                    connect_clicked(sender) => move |_| send!(sender, AppMsg::ShowAboutMouse)
                                 },
-                                connect_clicked(sender) => move |_| send!(sender, AppMsg::ShowAboutMouse)
-                            },
-                        },
-                        append = &gtk4::Box {
-                            set_margin_bottom: 18,
-                            set_spacing: 8,
-                            set_orientation: gtk4::Orientation::Horizontal,
-                            append = &gtk4::Label {
-                                set_label: "Configuration",
                             },
-                            append: profiles_stack = &gtk4::Stack {
-                                add_child: profiles_dropdown = &gtk4::DropDown {
-                                    set_hexpand: true,
-                                    // set_show_arrow: false, XXX requires GTK 4.6?
+                            append = &gtk4::Box {
+                                set_margin_bottom: 18,
+                                set_spacing: 8,
+                                set_orientation: gtk4::Orientation::Horizontal,
+                                append = &gtk4::Label {
+                                    set_label: "Configuration",
+                                },
+                                append: profiles_stack = &gtk4::Stack {
+                                    add_child: profiles_dropdown = &gtk4::DropDown {
+                                        set_hexpand: true,
+                                        // set_show_arrow: false, XXX requires GTK 4.6?
+                                    },
+                                    add_child: profiles_entry = &gtk4::Entry {
+                                        set_max_length: 30,
+                                        connect_activate(sender) => move |_| {
+                                            send!(sender, AppMsg::ToggleRenameConfig);
+                                        }
+                                    }
+
                                 },
-                                add_child: profiles_entry = &gtk4::Entry {
-                                    set_max_length: 30,
-                                    connect_activate(sender) => move |_| {
+                                append: rename_button = &gtk4::Button {
+                                    connect_clicked(sender) => move |_| {
                                         send!(sender, AppMsg::ToggleRenameConfig);
                                     }
+                                },
+                                append = &gtk4::MenuButton {
+                                    set_menu_model: Some(&profile_menu),
+                                    set_icon_name: "view-more-symbolic",
+                                    set_tooltip_text: Some("Manage Configurations"),
                                 }
-
                             },
-                            append: rename_button = &gtk4::Button {
-                                connect_clicked(sender) => move |_| {
-                                    send!(sender, AppMsg::ToggleRenameConfig);
+                            // One element box to work around weird size allocation behavior
+                            append = &gtk4::Box {
+                                set_margin_top: 6,
+                                set_margin_bottom: 6,
+                                set_vexpand: false,
+                                set_halign: gtk4::Align::Center,
+                                append = &gtk4::Overlay {
+                                    set_child: mouse_picture = Some(&gtk4::Picture) {
+                                        set_can_shrink: false,
+                                    },
+                                    add_overlay: buttons_widget = &ButtonsWidget {
+                                    },
+                                    set_measure_overlay: args!(&buttons_widget, false),
                                 }
-                            }
-                        },
-                        // One element box to work around weird size allocation behavior
-                        append = &gtk4::Box {
-                            set_margin_top: 6,
-                            set_margin_bottom: 6,
-                            set_vexpand: false,
-                            set_halign: gtk4::Align::Center,
-                            append = &gtk4::Overlay {
-                                set_child: mouse_picture = Some(&gtk4::Picture) {
-                                    set_can_shrink: false,
-                                },
-                                add_overlay: buttons_widget = &ButtonsWidget {
+                            },
+                            append = &gtk4::Label {
+                                set_label: "Select a button to change its binding. Your settings are automatically saved to firmware.",
+                                set_margin_bottom: 18,
+                            },
+                            append = &gtk4::Label {
+                                set_label: "Sensitivity",
+                                set_attributes = Some(&pango::AttrList) {
+                                    insert: pango::AttrInt::new_weight(pango::Weight::Bold)
                                 },
-                                set_measure_overlay: args!(&buttons_widget, false),
-                            }
-                        },
-                        append = &gtk4::Label {
-                            set_label: "Select a button to change its binding. Your settings are automatically saved to firmware.",
-                            set_margin_bottom: 18,
-                        },
-                        append = &gtk4::Label {
-                            set_label: "Sensitivity",
-                            set_attributes = Some(&pango::AttrList) {
-                                insert: pango::AttrInt::new_weight(pango::Weight::Bold)
+                                set_halign: gtk4::Align::Start
                             },
-                            set_halign: gtk4::Align::Start
-                        },
-                        append = &gtk4::ListBox {
-                            add_css_class: "frame",
-                            append = &gtk4::ListBoxRow {
-                                set_selectable: false,
-                                set_activatable: false,
-                                set_child = Some(&gtk4::Box) {
-                                    set_orientation: gtk4::Orientation::Horizontal,
-                                    set_margin_top: 6,
-                                    set_margin_bottom: 6,
-                                    set_margin_start: 6,
-                                    set_margin_end: 6,
-                                    append = &gtk4::Box {
-                                        set_margin_end: 36,
-                                        set_orientation: gtk4::Orientation::Vertical,
-                                        append = &gtk4::Label {
-                                            set_label: "Mouse Cursor Speed",
-                                            set_attributes = Some(&pango::AttrList) {
-                                                insert: pango::AttrInt::new_weight(pango::Weight::Bold)
+                            append = &gtk4::ListBox {
+                                add_css_class: "frame",
+                                append = &gtk4::ListBoxRow {
+                                    set_selectable: false,
+                                    set_activatable: false,
+                                    set_child = Some(&gtk4::Box) {
+                                        set_orientation: gtk4::Orientation::Horizontal,
+                                        set_margin_top: 6,
+                                        set_margin_bottom: 6,
+                                        set_margin_start: 6,
+                                        set_margin_end: 6,
+                                        append = &gtk4::Box {
+                                            set_margin_end: 36,
+                                            set_orientation: gtk4::Orientation::Vertical,
+                                            append = &gtk4::Label {
+                                                set_label: "Mouse Cursor Speed",
+                                                set_attributes = Some(&pango::AttrList) {
+                                                    insert: pango::AttrInt::new_weight(pango::Weight::Bold)
+                                                }
+                                            },
+                                            append = &gtk4::Label {
+                                                set_label: "Sensitivity (DPI)",
                                             }
                                         },
                                         append = &gtk4::Label {
-                                            set_label: "Sensitivity (DPI)",
-                                        }
-                                    },
-                                    append = &gtk4::Label {
-                                        set_label: watch! { &model.device().map_or_else(String::new, |device| format!("{}", device.round_dpi(device.config.dpi))) },
-                                    },
-                                    append: dpi_scale = &gtk4::Scale {
-                                        set_hexpand: true,
-                                        set_adjustment: &gtk4::Adjustment::new(800., 800., 3000., DPI_STEP, DPI_STEP, 0.), // XXX don't hard-code?
-                                        set_value: watch! { model.device().map_or(0., |device| device.config.dpi) },
-                                        connect_change_value(sender) => move |_, _, value| {
-                                            send!(sender, AppMsg::SetDpi(value));
-                                            gtk4::Inhibit(false)
+                                            set_label: watch! { &model.device().map_or_else(String::new, |device| format!("{}", round_dpi(device.profile().dpi))) },
+                                        },
+                                        append: dpi_scale = &gtk4::Scale {
+                                            set_hexpand: true,
+                                            set_adjustment: &dpi_adjustment(),
+                                            set_sensitive: !policy().dpi_locked(),
+                                            set_value: watch! { model.device().map_or(0., |device| device.profile().dpi) },
+                                            connect_change_value(sender) => move |_, _, value| {
+                                                send!(sender, AppMsg::SetDpi(value));
+                                                gtk4::Inhibit(false)
+                                            }
                                         }
                                     }
                                 }
@@ -634,9 +1429,19 @@ impl Widgets<AppModel, ()> for AppWidgets {
         menu: {
             "Import Configuration" => ImportConfig,
             "Export Configuration" => ExportConfig,
+            "Application Rules" => RulesAction,
             "Reset to Default" => ResetAction,
             "Remove Device" => RemoveAction,
             "About" => AboutAction,
+        },
+        profile_menu: {
+            "New Configuration" => AddProfileAction,
+            "Duplicate Configuration" => DuplicateProfileAction,
+            "Move Up" => MoveProfileUpAction,
+            "Move Down" => MoveProfileDownAction,
+            "Export to File…" => ExportProfileAction,
+            "Save to Library" => SaveToLibraryAction,
+            "Delete Configuration" => RemoveProfileAction,
         }
     }
 
@@ -646,6 +1451,7 @@ impl Widgets<AppModel, ()> for AppWidgets {
         desktop_settings: gio::Settings,
         device_actions: gio::SimpleActionGroup,
         profiles_dropdown_signal: glib::SignalHandlerId,
+        config_monitor: Option<gio::FileMonitor>,
     }
 
     fn post_init() {
@@ -726,6 +1532,24 @@ impl Widgets<AppModel, ()> for AppWidgets {
             }),
         );
         device_group.add_action(export_action);
+        let export_profile_action: RelmAction<ExportProfileAction> = RelmAction::new_stateless(
+            glib::clone!(@strong main_window, @strong sender => move |_| {
+                show_export_dialog(&main_window, glib::clone!(@strong sender => move |path| {
+                    send!(sender, AppMsg::ExportProfile(path));
+                }));
+            }),
+        );
+        device_group.add_action(export_profile_action);
+        let save_to_library_action: RelmAction<SaveToLibraryAction> =
+            RelmAction::new_stateless(glib::clone!(@strong sender => move |_| {
+                send!(sender, AppMsg::SaveProfileToLibrary);
+            }));
+        device_group.add_action(save_to_library_action);
+        let rules_action: RelmAction<RulesAction> =
+            RelmAction::new_stateless(glib::clone!(@strong sender => move |_| {
+                send!(sender, AppMsg::ShowRules);
+            }));
+        device_group.add_action(rules_action);
         let reset_action: RelmAction<ResetAction> = RelmAction::new_stateless(
             glib::clone!(@strong main_window, @strong sender => move |_| {
                 show_prompt_dialog(&main_window, "Reset sensitivity and all configurations for this device?",
@@ -745,6 +1569,35 @@ impl Widgets<AppModel, ()> for AppWidgets {
             }),
         );
         device_group.add_action(remove_action);
+        let add_profile_action: RelmAction<AddProfileAction> =
+            RelmAction::new_stateless(glib::clone!(@strong sender => move |_| {
+                send!(sender, AppMsg::AddProfile);
+            }));
+        device_group.add_action(add_profile_action);
+        let duplicate_profile_action: RelmAction<DuplicateProfileAction> =
+            RelmAction::new_stateless(glib::clone!(@strong sender => move |_| {
+                send!(sender, AppMsg::DuplicateProfile);
+            }));
+        device_group.add_action(duplicate_profile_action);
+        let move_profile_up_action: RelmAction<MoveProfileUpAction> =
+            RelmAction::new_stateless(glib::clone!(@strong sender => move |_| {
+                send!(sender, AppMsg::MoveProfile(true));
+            }));
+        device_group.add_action(move_profile_up_action);
+        let move_profile_down_action: RelmAction<MoveProfileDownAction> =
+            RelmAction::new_stateless(glib::clone!(@strong sender => move |_| {
+                send!(sender, AppMsg::MoveProfile(false));
+            }));
+        device_group.add_action(move_profile_down_action);
+        let remove_profile_action: RelmAction<RemoveProfileAction> = RelmAction::new_stateless(
+            glib::clone!(@strong main_window, @strong sender => move |_| {
+                show_prompt_dialog(&main_window, "Delete this configuration?",
+                    glib::clone!(@strong sender => move || {
+                        send!(sender, AppMsg::RemoveProfile);
+                    }));
+            }),
+        );
+        device_group.add_action(remove_profile_action);
 
         let app_actions = app_group.into_action_group();
         let device_actions = device_group.into_action_group();
@@ -752,12 +1605,36 @@ impl Widgets<AppModel, ()> for AppWidgets {
 
         send!(sender, AppMsg::SetDeviceMonitor);
 
+        focus::spawn_focus_thread(sender.clone());
+
+        if let Some(connection) = &model.dbus_connection {
+            if let Err(err) = dbus::register(connection, sender.clone()) {
+                eprintln!("Failed to register D-Bus interface: {}", err);
+            }
+        }
+
+        // In case the device doesn't report its battery level itself
+        glib::timeout_add_seconds(
+            BATTERY_QUERY_INTERVAL,
+            glib::clone!(@strong sender => move || {
+                glib::Continue(sender.send(AppMsg::QueryBattery).is_ok())
+            }),
+        );
+
         glib::timeout_add_seconds(
             10,
             glib::clone!(@strong sender => move || {
                 glib::Continue(sender.send(AppMsg::SaveConfig).is_ok())
             }),
         );
+
+        let config_monitor = match monitor_config_dir(sender.clone()) {
+            Ok(monitor) => Some(monitor),
+            Err(err) => {
+                eprintln!("Failed to monitor config directory: {}", err);
+                None
+            }
+        };
     }
 
     fn post_view() {
@@ -765,7 +1642,10 @@ impl Widgets<AppModel, ()> for AppWidgets {
             show_error_dialog(&main_window, error);
         }
 
-        if model.selected_device.is_some() {
+        if model.device_monitor_error.is_some() {
+            self.stack.set_visible_child(&self.no_permission_page);
+            main_window.insert_action_group("device", None::<&gio::ActionGroup>);
+        } else if model.selected_device.is_some() {
             let connected = model.device().map_or(false, |x| x.state.connected);
             self.device_actions
                 .lookup_action("remove")
@@ -773,6 +1653,22 @@ impl Widgets<AppModel, ()> for AppWidgets {
                 .downcast_ref::<gio::SimpleAction>()
                 .unwrap()
                 .set_enabled(!connected);
+            if let Some(device) = model.device() {
+                let profile = device.config.profile_num();
+                let num_profiles = device.config.profiles().len();
+                for (name, enabled) in [
+                    ("remove_profile", num_profiles > 1),
+                    ("move_profile_up", profile > 0),
+                    ("move_profile_down", profile + 1 < num_profiles),
+                ] {
+                    self.device_actions
+                        .lookup_action(name)
+                        .unwrap()
+                        .downcast_ref::<gio::SimpleAction>()
+                        .unwrap()
+                        .set_enabled(enabled);
+                }
+            }
 
             self.stack.set_visible_child(&self.device_page);
             let in_rename_config = self.profiles_stack.visible_child().as_ref()
@@ -824,12 +1720,22 @@ impl Widgets<AppModel, ()> for AppWidgets {
                         set_selectable: false,
                         set_activatable: true,
                         set_child = Some(&gtk4::Box) {
-                            set_orientation: gtk4::Orientation::Vertical,
-                            append = &gtk4::Label {
-                                set_label: "HP 930 series Creator Wireless Mouse" // TODO don't hard-code
+                            set_orientation: gtk4::Orientation::Horizontal,
+                            set_spacing: 12,
+                            append = &gtk4::Box {
+                                set_orientation: gtk4::Orientation::Vertical,
+                                set_hexpand: true,
+                                append = &gtk4::Label {
+                                    set_label: "HP 930 series Creator Wireless Mouse" // TODO don't hard-code
+                                },
+                                append = &gtk4::Label {
+                                    set_label: &format!("Unique ID: {}", device.serial )
+                                }
                             },
-                            append = &gtk4::Label {
-                                set_label: &format!("Unique ID: {}", device.serial )
+                            append = &gtk4::Image {
+                                set_visible: device.state.connected,
+                                set_from_icon_name: Some(&battery::icon_name(device.state.battery_percent)),
+                                set_tooltip_text: device.state.battery_percent.map(|x| format!("{}%", x)).as_deref(),
                             }
                         }
                     }
@@ -840,34 +1746,37 @@ impl Widgets<AppModel, ()> for AppWidgets {
 
         if let Some(device) = model.device() {
             if self.first_view_run || model.bindings_changed {
-                let bindings = &device.config.profile().bindings;
+                let profile = device.profile();
                 for (id, button) in &self.buttons {
+                    let locked = match model.swap_buttons(*id) {
+                        Some(id) => policy().button_locked(id),
+                        None => policy().left_handed_locked(),
+                    };
+                    button.set_sensitive(!locked);
                     if let Some(id) = model.swap_buttons(*id) {
                         button.set_label(
-                            &bindings
+                            &profile
+                                .bindings
                                 .get(&id)
                                 .map_or_else(|| id.def_binding().label.to_string(), |x| x.label()),
                         );
+                        let tooltip = if device.state.mouse_bindings.contains_key(&id) {
+                            Some("Set on the mouse by another program, and not saved")
+                        } else {
+                            None
+                        };
+                        button.set_tooltip_text(tooltip);
                     } else {
                         button.set_label("Left Click");
+                        button.set_tooltip_text(None);
                     }
                 }
             }
 
             if self.first_view_run || model.profiles_changed {
-                let default_labels = &[
-                    "Configuration One",
-                    "Configuration Two",
-                    "Configuration Three",
-                    "Configuration Four",
-                ];
-                let labels: Vec<_> = device
-                    .config
-                    .profiles()
-                    .iter()
-                    .enumerate()
-                    .map(|(n, profile)| profile.name.as_deref().unwrap_or(default_labels[n]))
-                    .collect();
+                let mut labels = device.config.profile_labels();
+                labels.extend(model.library.iter().map(|(label, _)| label.clone()));
+                let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
                 self.profiles_dropdown
                     .block_signal(&self.profiles_dropdown_signal);
                 self.profiles_dropdown
@@ -879,11 +1788,16 @@ impl Widgets<AppModel, ()> for AppWidgets {
             }
 
             if model.show_about_mouse {
+                let battery_history = battery_history::load(&device.serial).unwrap_or_else(|err| {
+                    eprintln!("Failed to load battery history: {}", err);
+                    Vec::new()
+                });
                 show_info_dialog(
                     &main_window,
                     &device.config.device,
                     &device.serial,
                     device.state.firmware_version,
+                    battery_history,
                 );
             }
         }
@@ -898,21 +1812,81 @@ relm4::new_stateless_action!(AboutAction, AppActionGroup, "about");
 relm4::new_action_group!(DeviceActionGroup, "device");
 relm4::new_stateless_action!(ImportConfig, DeviceActionGroup, "import_config");
 relm4::new_stateless_action!(ExportConfig, DeviceActionGroup, "export_config");
+relm4::new_stateless_action!(RulesAction, DeviceActionGroup, "rules");
 relm4::new_stateless_action!(ResetAction, DeviceActionGroup, "reset_config");
 relm4::new_stateless_action!(RemoveAction, DeviceActionGroup, "remove");
+relm4::new_stateless_action!(AddProfileAction, DeviceActionGroup, "add_profile");
+relm4::new_stateless_action!(
+    DuplicateProfileAction,
+    DeviceActionGroup,
+    "duplicate_profile"
+);
+relm4::new_stateless_action!(MoveProfileUpAction, DeviceActionGroup, "move_profile_up");
+relm4::new_stateless_action!(
+    MoveProfileDownAction,
+    DeviceActionGroup,
+    "move_profile_down"
+);
+relm4::new_stateless_action!(RemoveProfileAction, DeviceActionGroup, "remove_profile");
+relm4::new_stateless_action!(ExportProfileAction, DeviceActionGroup, "export_profile");
+relm4::new_stateless_action!(SaveToLibraryAction, DeviceActionGroup, "save_to_library");
+
+// Range of the DPI scale, limited by the policy
+fn dpi_adjustment() -> gtk4::Adjustment {
+    let (min, max) = policy().dpi_range(800., 3000.); // XXX don't hard-code?
+    gtk4::Adjustment::new(min, min, max, DPI_STEP, DPI_STEP, 0.)
+}
+
+// Import a full config, or a single profile as a config with one profile
+fn import_file(path: &Path) -> Result<MouseConfig, String> {
+    if let Ok(file) = ProfileFile::load(path) {
+        let mut config = MouseConfig::new(file.model.clone());
+        config.profiles_mut()[0] = file.profile();
+        return Ok(config);
+    }
+    MouseConfig::import(path)
+}
+
+// Reload config files edited by hand, or by the CLI
+fn monitor_config_dir(sender: Sender<AppMsg>) -> Result<gio::FileMonitor, String> {
+    let dir = config::devices_config_dir();
+    fs::create_dir_all(&dir).map_err(|x| x.to_string())?;
+    let monitor = gio::File::for_path(&dir)
+        .monitor_directory(
+            gio::FileMonitorFlags::WATCH_MOVES,
+            None::<&gio::Cancellable>,
+        )
+        .map_err(|x| x.to_string())?;
+    monitor.connect_changed(move |_, file, other_file, event| {
+        let files = match event {
+            // Wait for `ChangesDoneHint`, so a partly written file isn't read
+            gio::FileMonitorEvent::Changed | gio::FileMonitorEvent::AttributeChanged => return,
+            gio::FileMonitorEvent::Renamed => [Some(file), other_file],
+            _ => [Some(file), None],
+        };
+        for path in files.iter().flatten().filter_map(|x| x.path()) {
+            if let Some(serial) = config::serial_for_path(&path) {
+                send!(sender, AppMsg::ConfigFileChanged(serial));
+            }
+        }
+    });
+    Ok(monitor)
+}
 
 fn main() {
     let mut args = env::args().skip(1);
     match args.next().as_deref() {
+        #[cfg(feature = "appimage")]
         Some("--device-monitor") => {
-            device_monitor_process::device_monitor_process();
+            mouse_configurator::monitor_protocol::serve(libc::STDIN_FILENO);
             return;
         }
         Some("--add-fake-device") => {
-            let mut configs = load_config();
             let serial = format!("FAKE{:16X}", rand::random::<u64>());
-            configs.insert(serial, MouseConfig::new("Brain".to_string()));
-            save_config(configs.iter());
+            let config = MouseConfig::new("Brain".to_string());
+            if let Err(err) = config::save_device_config(&serial, &config) {
+                eprintln!("{}", err);
+            }
         }
         _ => {}
     }
@@ -936,18 +1910,16 @@ fn main() {
         gtk4::STYLE_PROVIDER_PRIORITY_APPLICATION,
     );
 
-    glib::set_prgname(Some("org.pop_os.mouseconfigurator"));
+    glib::set_prgname(Some(APP_ID));
     glib::set_application_name("Mouse Configurator");
-    let app = gtk4::Application::builder()
-        .application_id("org.pop_os.mouseconfigurator")
-        .build();
+    let app = gtk4::Application::builder().application_id(APP_ID).build();
     app.register(None::<&gio::Cancellable>).unwrap();
-    let device_monitor = if !app.is_remote() {
-        Some(device_monitor_process::DeviceMonitorProcess::new().unwrap())
+    let (device_monitor, dbus_connection) = if !app.is_remote() {
+        (Some(open_device_monitor()), app.dbus_connection())
     } else {
-        None
+        (None, None)
     };
 
-    let app = RelmApp::with_app(AppModel::new(device_monitor), app);
+    let app = RelmApp::with_app(AppModel::new(device_monitor, dbus_connection), app);
     app.run();
 }
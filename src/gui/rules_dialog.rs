@@ -0,0 +1,193 @@
+use gtk4::{glib, prelude::*};
+use relm4::{send, view, ComponentUpdate, Model, Sender, Widgets};
+
+use crate::{util, AppMsg};
+use mouse_configurator::config::AppRule;
+
+pub enum RulesDialogMsg {
+    Show(Vec<AppRule>, Vec<String>),
+    Add(String),
+    Remove(usize),
+    SetProfile(usize, usize),
+    Close,
+}
+
+#[derive(Default)]
+pub struct RulesDialogModel {
+    shown: bool,
+    rules: Vec<AppRule>,
+    profile_labels: Vec<String>,
+    rules_changed: bool,
+}
+
+impl RulesDialogModel {
+    fn rules_updated(&mut self, parent_sender: &Sender<AppMsg>) {
+        self.rules_changed = true;
+        send!(parent_sender, AppMsg::SetRules(self.rules.clone()));
+    }
+}
+
+impl Model for RulesDialogModel {
+    type Msg = RulesDialogMsg;
+    type Widgets = RulesDialogWidgets;
+    type Components = ();
+}
+
+impl ComponentUpdate<super::AppModel> for RulesDialogModel {
+    fn init_model(_parent_model: &super::AppModel) -> Self {
+        RulesDialogModel::default()
+    }
+
+    fn update(
+        &mut self,
+        msg: RulesDialogMsg,
+        _components: &(),
+        _sender: Sender<RulesDialogMsg>,
+        parent_sender: Sender<AppMsg>,
+    ) {
+        self.rules_changed = false;
+
+        match msg {
+            RulesDialogMsg::Show(rules, profile_labels) => {
+                self.rules = rules;
+                self.profile_labels = profile_labels;
+                self.rules_changed = true;
+                self.shown = true;
+            }
+            RulesDialogMsg::Add(app_id) => {
+                let app_id = app_id.trim();
+                if !app_id.is_empty() {
+                    self.rules.push(AppRule {
+                        app_id: app_id.to_string(),
+                        profile: 0,
+                    });
+                    self.rules_updated(&parent_sender);
+                }
+            }
+            RulesDialogMsg::Remove(idx) => {
+                if idx < self.rules.len() {
+                    self.rules.remove(idx);
+                    self.rules_updated(&parent_sender);
+                }
+            }
+            RulesDialogMsg::SetProfile(idx, profile) => {
+                if let Some(rule) = self.rules.get_mut(idx) {
+                    if rule.profile != profile {
+                        rule.profile = profile;
+                        send!(parent_sender, AppMsg::SetRules(self.rules.clone()));
+                    }
+                }
+            }
+            RulesDialogMsg::Close => {
+                self.shown = false;
+            }
+        }
+    }
+}
+
+#[relm4::widget(pub)]
+impl Widgets<RulesDialogModel, super::AppModel> for RulesDialogWidgets {
+    view! {
+        gtk4::Dialog {
+            set_transient_for: parent!(Some(&parent_widgets.main_window)),
+            set_default_size: args!(400, 300),
+            set_modal: true,
+            set_hide_on_close: true,
+            set_title: Some("Application Rules"),
+            set_visible: watch!(model.shown),
+            connect_close_request(sender) => move |_| {
+                send!(sender, RulesDialogMsg::Close);
+                gtk4::Inhibit(false)
+            },
+            set_child = Some(&gtk4::Box) {
+                set_orientation: gtk4::Orientation::Vertical,
+                set_margin_start: 12,
+                set_margin_end: 12,
+                set_margin_top: 12,
+                set_margin_bottom: 12,
+                set_spacing: 12,
+                append = &gtk4::Label {
+                    set_label: "Switch to a configuration while an application is focused. Applications are matched by their app ID or window class.",
+                    set_wrap: true,
+                },
+                append: list_box = &gtk4::ListBox {
+                    add_css_class: "frame",
+                    set_selection_mode: gtk4::SelectionMode::None,
+                    set_header_func: util::header_func,
+                    set_placeholder = Some(&gtk4::Label) {
+                        set_margin_top: 12,
+                        set_margin_bottom: 12,
+                        set_label: "No Rules",
+                    },
+                },
+                append = &gtk4::Box {
+                    set_orientation: gtk4::Orientation::Horizontal,
+                    set_spacing: 6,
+                    append: entry = &gtk4::Entry {
+                        set_hexpand: true,
+                        set_placeholder_text: Some("Application ID, like org.kde.krita"),
+                        connect_activate(sender) => move |entry| {
+                            send!(sender, RulesDialogMsg::Add(entry.text().to_string()));
+                            entry.set_text("");
+                        }
+                    },
+                    append = &gtk4::Button {
+                        set_label: "Add",
+                        connect_clicked(sender, entry) => move |_| {
+                            send!(sender, RulesDialogMsg::Add(entry.text().to_string()));
+                            entry.set_text("");
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn post_view() {
+        if !model.rules_changed {
+            return;
+        }
+
+        while let Some(row) = self.list_box.row_at_index(0) {
+            self.list_box.remove(&row);
+        }
+
+        let profile_labels: Vec<&str> = model.profile_labels.iter().map(String::as_str).collect();
+        for (idx, rule) in model.rules.iter().enumerate() {
+            view! {
+                row = gtk4::ListBoxRow {
+                    set_selectable: false,
+                    set_activatable: false,
+                    set_child = Some(&gtk4::Box) {
+                        set_orientation: gtk4::Orientation::Horizontal,
+                        set_margin_top: 6,
+                        set_margin_bottom: 6,
+                        set_margin_start: 6,
+                        set_margin_end: 6,
+                        set_spacing: 12,
+                        append = &gtk4::Label {
+                            set_label: &rule.app_id,
+                            set_hexpand: true,
+                            set_halign: gtk4::Align::Start,
+                        },
+                        append: dropdown = &gtk4::DropDown {
+                            set_model: Some(&gtk4::StringList::new(&profile_labels)),
+                            set_selected: rule.profile as u32,
+                        },
+                        append = &gtk4::Button {
+                            add_css_class: "flat",
+                            set_icon_name: "user-trash-symbolic",
+                            connect_clicked(sender) => move |_| {
+                                send!(sender, RulesDialogMsg::Remove(idx));
+                            }
+                        }
+                    }
+                }
+            }
+            dropdown.connect_selected_notify(glib::clone!(@strong sender => move |dropdown| {
+                send!(sender, RulesDialogMsg::SetProfile(idx, dropdown.selected() as usize));
+            }));
+            self.list_box.append(&row);
+        }
+    }
+}
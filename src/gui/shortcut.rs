@@ -0,0 +1,263 @@
+//! Keyboard shortcuts entered in the binding dialog, and translation from GDK
+//! key events to the HID usages sent by the mouse.
+
+use gtk4::gdk;
+use mouse_configurator::{
+    keycode::{Key, Modifiers},
+    shortcut::Shortcut,
+};
+
+// Keyboard/Keypad page usage of each evdev key code. The mouse sends
+// scancodes, which the host translates with its own layout, so the
+// shortcut is captured by physical key rather than by keyval.
+static KEYS: &[(u32, Key)] = &[
+    (1, Key::Esc),
+    (2, Key::Digit1),
+    (3, Key::Digit2),
+    (4, Key::Digit3),
+    (5, Key::Digit4),
+    (6, Key::Digit5),
+    (7, Key::Digit6),
+    (8, Key::Digit7),
+    (9, Key::Digit8),
+    (10, Key::Digit9),
+    (11, Key::Digit0),
+    (12, Key::Minus),
+    (13, Key::Equal),
+    (14, Key::Backspace),
+    (15, Key::Tab),
+    (16, Key::Q),
+    (17, Key::W),
+    (18, Key::E),
+    (19, Key::R),
+    (20, Key::T),
+    (21, Key::Y),
+    (22, Key::U),
+    (23, Key::I),
+    (24, Key::O),
+    (25, Key::P),
+    (26, Key::LeftBrace),
+    (27, Key::RightBrace),
+    (28, Key::Enter),
+    (30, Key::A),
+    (31, Key::S),
+    (32, Key::D),
+    (33, Key::F),
+    (34, Key::G),
+    (35, Key::H),
+    (36, Key::J),
+    (37, Key::K),
+    (38, Key::L),
+    (39, Key::Semicolon),
+    (40, Key::Apostrophe),
+    (41, Key::Grave),
+    (43, Key::Backslash),
+    (44, Key::Z),
+    (45, Key::X),
+    (46, Key::C),
+    (47, Key::V),
+    (48, Key::B),
+    (49, Key::N),
+    (50, Key::M),
+    (51, Key::Comma),
+    (52, Key::Dot),
+    (53, Key::Slash),
+    (55, Key::KpAsterisk),
+    (57, Key::Space),
+    (58, Key::CapsLock),
+    (59, Key::F1),
+    (60, Key::F2),
+    (61, Key::F3),
+    (62, Key::F4),
+    (63, Key::F5),
+    (64, Key::F6),
+    (65, Key::F7),
+    (66, Key::F8),
+    (67, Key::F9),
+    (68, Key::F10),
+    (69, Key::NumLock),
+    (70, Key::ScrollLock),
+    (71, Key::Kp7),
+    (72, Key::Kp8),
+    (73, Key::Kp9),
+    (74, Key::KpMinus),
+    (75, Key::Kp4),
+    (76, Key::Kp5),
+    (77, Key::Kp6),
+    (78, Key::KpPlus),
+    (79, Key::Kp1),
+    (80, Key::Kp2),
+    (81, Key::Kp3),
+    (82, Key::Kp0),
+    (83, Key::KpDot),
+    (85, Key::ZenkakuHankaku),
+    (86, Key::Key102nd),
+    (87, Key::F11),
+    (88, Key::F12),
+    (89, Key::Ro),
+    (90, Key::Katakana),
+    (91, Key::Hiragana),
+    (92, Key::Henkan),
+    (93, Key::KatakanaHiragana),
+    (94, Key::Muhenkan),
+    (95, Key::KpJpComma),
+    (96, Key::KpEnter),
+    (98, Key::KpSlash),
+    (99, Key::SysRq),
+    (102, Key::Home),
+    (103, Key::Up),
+    (104, Key::PageUp),
+    (105, Key::Left),
+    (106, Key::Right),
+    (107, Key::End),
+    (108, Key::Down),
+    (109, Key::PageDown),
+    (110, Key::Insert),
+    (111, Key::Delete),
+    (113, Key::Mute),
+    (114, Key::VolumeDown),
+    (115, Key::VolumeUp),
+    (116, Key::Power),
+    (117, Key::KpEqual),
+    (119, Key::Pause),
+    (121, Key::KpComma),
+    (122, Key::Hangeul),
+    (123, Key::Hanja),
+    (124, Key::Yen),
+    (127, Key::Compose),
+    (128, Key::Stop),
+    (129, Key::Again),
+    (130, Key::Props),
+    (131, Key::Undo),
+    (132, Key::Front),
+    (133, Key::Copy),
+    (134, Key::Open),
+    (135, Key::Paste),
+    (136, Key::Find),
+    (137, Key::Cut),
+    (138, Key::Help),
+    (183, Key::F13),
+    (184, Key::F14),
+    (185, Key::F15),
+    (186, Key::F16),
+    (187, Key::F17),
+    (188, Key::F18),
+    (189, Key::F19),
+    (190, Key::F20),
+    (191, Key::F21),
+    (192, Key::F22),
+    (193, Key::F23),
+    (194, Key::F24),
+];
+
+// X11 and Wayland hardware keycodes are evdev codes offset by 8
+const EVDEV_OFFSET: u32 = 8;
+
+static MODIFIERS: &[(Modifiers, gdk::ModifierType)] = &[
+    (Modifiers::CTRL, gdk::ModifierType::CONTROL_MASK),
+    (Modifiers::SHIFT, gdk::ModifierType::SHIFT_MASK),
+    (Modifiers::ALT, gdk::ModifierType::ALT_MASK),
+    (Modifiers::SUPER, gdk::ModifierType::SUPER_MASK),
+];
+
+// Keys that only change the modifier state, and can't be bound on their own
+static MODIFIER_KEYS: &[gdk::Key] = &[
+    gdk::Key::Shift_L,
+    gdk::Key::Shift_R,
+    gdk::Key::Control_L,
+    gdk::Key::Control_R,
+    gdk::Key::Alt_L,
+    gdk::Key::Alt_R,
+    gdk::Key::Meta_L,
+    gdk::Key::Meta_R,
+    gdk::Key::Super_L,
+    gdk::Key::Super_R,
+    gdk::Key::Hyper_L,
+    gdk::Key::Hyper_R,
+    gdk::Key::ISO_Level3_Shift,
+];
+
+/// A captured shortcut, with a label for the symbol the key produces in the
+/// current layout.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CapturedShortcut {
+    pub shortcut: Shortcut,
+    pub label: String,
+}
+
+// Keys that produce a different symbol depending on the layout
+fn layout_dependent(key: Key) -> bool {
+    matches!(key.usage(), 0x04..=0x27 | 0x2D..=0x38) || key == Key::Key102nd
+}
+
+/// Translate a key press to a shortcut. Returns `None` for modifier keys,
+/// or keys that have no HID usage.
+pub fn from_key_event(
+    keycode: u32,
+    keyval: gdk::Key,
+    state: gdk::ModifierType,
+) -> Option<CapturedShortcut> {
+    if MODIFIER_KEYS.contains(&keyval) {
+        return None;
+    }
+    let evdev = keycode.checked_sub(EVDEV_OFFSET)?;
+    let (_, key) = KEYS.iter().find(|(code, _)| *code == evdev)?;
+    let modifiers = MODIFIERS
+        .iter()
+        .filter(|(_, mask)| state.contains(*mask))
+        .fold(Modifiers::empty(), |modifiers, (modifier, _)| {
+            modifiers | *modifier
+        });
+    // Label with the symbol from the keyval, since the name of the usage is
+    // the symbol on a US layout
+    let key_label = keyval
+        .to_upper()
+        .to_unicode()
+        .filter(|c| layout_dependent(*key) && !c.is_control() && !c.is_whitespace())
+        .map_or_else(|| key.label().to_string(), |c| c.to_string());
+    let mut labels = modifiers.labels().collect::<Vec<_>>();
+    labels.push(&key_label);
+    Some(CapturedShortcut {
+        shortcut: Shortcut {
+            modifiers: modifiers.bits(),
+            key: key.usage(),
+        },
+        label: labels.join("+"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use mouse_configurator::button::{decode_action, encode_action};
+
+    use super::*;
+
+    #[test]
+    fn shortcut_from_key_event() {
+        let state = gdk::ModifierType::CONTROL_MASK | gdk::ModifierType::SHIFT_MASK;
+        let captured = from_key_event(30 + EVDEV_OFFSET, gdk::Key::A, state).unwrap();
+        assert_eq!(captured.label, "Ctrl+Shift+A");
+        let shortcut = captured.shortcut;
+        assert_eq!(shortcut.label(), "Ctrl+Shift+A");
+        assert_eq!(
+            Shortcut::from_binding(&decode_action(&encode_action(&shortcut.binding())).unwrap()),
+            Some(shortcut)
+        );
+        assert_eq!(
+            from_key_event(29 + EVDEV_OFFSET, gdk::Key::Control_L, state),
+            None
+        );
+    }
+
+    #[test]
+    fn shortcut_from_key_event_layout() {
+        // The key in the position of Q on an AZERTY layout
+        let state = gdk::ModifierType::CONTROL_MASK;
+        let captured = from_key_event(16 + EVDEV_OFFSET, gdk::Key::a, state).unwrap();
+        assert_eq!(captured.shortcut.key, Key::Q.usage());
+        assert_eq!(captured.label, "Ctrl+A");
+        // Keys with the same symbol on every layout use the usage name
+        let captured = from_key_event(79 + EVDEV_OFFSET, gdk::Key::KP_1, state).unwrap();
+        assert_eq!(captured.label, format!("Ctrl+{}", Key::Kp1.label()));
+    }
+}
@@ -5,36 +5,159 @@ use nix::{
 };
 use relm4::{send, ComponentUpdate, Model, Sender};
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
+    fmt, io,
     os::unix::io::AsRawFd,
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
     },
     thread,
+    time::{Duration, Instant},
 };
 
-use super::{AppMsg, DeviceMonitorProcess};
-use mouse_configurator::{Button, Event, HpMouse, HpMouseEvents, ReadRes};
+use super::{
+    device_monitor_process::{DeviceEvent, DeviceMonitor},
+    AppMsg,
+};
+use mouse_configurator::{
+    bindings::HardwareButton, Button, Event, HpMouse, HpMouseEvents, PressType, ReadRes,
+};
 
 // Opaque ID, unique for one run of program, associated with one device
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct DeviceId(usize);
 
+/// HID operation on a device, which can be retried if it fails
+#[derive(Debug)]
+pub enum DeviceOperation {
+    QueryFirmware,
+    QueryState,
+    QueryBattery,
+    SetDpi(u16),
+    SetLeftHanded(bool),
+    SetBinding(Button),
+    // Read back settings, so `AppModel` can compare them to the configuration
+    Verify,
+}
+
+impl DeviceOperation {
+    fn is_write(&self) -> bool {
+        matches!(
+            self,
+            Self::SetDpi(_) | Self::SetLeftHanded(_) | Self::SetBinding(_)
+        )
+    }
+}
+
+impl fmt::Display for DeviceOperation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::QueryFirmware => write!(f, "read firmware version"),
+            Self::QueryState => write!(f, "read settings"),
+            Self::QueryBattery => write!(f, "read battery level"),
+            Self::SetDpi(dpi) => write!(f, "set DPI to {}", dpi),
+            Self::SetLeftHanded(true) => write!(f, "enable left handed mode"),
+            Self::SetLeftHanded(false) => write!(f, "disable left handed mode"),
+            Self::SetBinding(button) => match HardwareButton::from_u8(button.id) {
+                Some(id) => write!(f, "set binding of {} button", id.name()),
+                None => write!(f, "set binding of button {}", button.id),
+            },
+            Self::Verify => write!(f, "verify settings"),
+        }
+    }
+}
+
+/// Failed operation, sent to `AppModel` so it can be shown and retried
+#[derive(Debug)]
+pub struct DeviceError {
+    pub operation: DeviceOperation,
+    pub message: String,
+}
+
+impl fmt::Display for DeviceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Failed to {}: {}", self.operation, self.message)
+    }
+}
+
 pub enum WorkerMsg {
-    SetDeviceMonitor(DeviceMonitorProcess),
+    SetDeviceMonitor(DeviceMonitor),
     AddDevice(PathBuf, HpMouse),
+    RemoveDevice(PathBuf),
+    DeviceChanged(PathBuf),
+    MonitorError(String),
+    MonitorStopped(String),
     Disconnect(DeviceId),
     SetDpi(DeviceId, u16),
     SetLeftHanded(DeviceId, bool),
     SetBinding(DeviceId, Button),
     HasFirmware(DeviceId),
+    // Perform an operation, like retrying one that failed
+    Run(DeviceId, DeviceOperation),
+    // Sent `VERIFY_DELAY` after a write; ignored if there were writes since
+    Verify(DeviceId, u64),
+    // Write queued changes to the device
+    Flush(DeviceId),
+}
+
+// Minimum time between batches of writes to a device. Each write is saved
+// to flash, and dragging the DPI scale produces many of them.
+const WRITE_INTERVAL: Duration = Duration::from_millis(250);
+
+// Wait for a batch of writes to finish before reading settings back
+const VERIFY_DELAY: Duration = Duration::from_secs(1);
+
+struct WorkerDevice {
+    path: PathBuf,
+    mouse: HpMouse,
+    // Cleared to stop the reader thread
+    running: Arc<AtomicBool>,
+    // Number of writes, so verification waits for the last in a batch
+    writes: u64,
+    queue: WriteQueue,
+}
+
+// Writes not yet sent to the device. A newer write to the same setting
+// replaces an older one.
+#[derive(Default)]
+struct WriteQueue {
+    bindings: BTreeMap<(u8, PressType), Button>,
+    left_handed: Option<bool>,
+    dpi: Option<u16>,
+    flush_scheduled: bool,
+    last_flush: Option<Instant>,
+}
+
+impl WriteQueue {
+    fn push(&mut self, operation: DeviceOperation) {
+        match operation {
+            DeviceOperation::SetBinding(button) => {
+                self.bindings.insert((button.id, button.press_type), button);
+            }
+            DeviceOperation::SetLeftHanded(value) => self.left_handed = Some(value),
+            DeviceOperation::SetDpi(value) => self.dpi = Some(value),
+            _ => unreachable!("`{}` is not a write", operation),
+        }
+    }
+
+    // Take queued writes, in the order they should be sent
+    fn take(&mut self) -> Vec<DeviceOperation> {
+        let bindings = std::mem::take(&mut self.bindings);
+        let mut operations: Vec<_> = bindings
+            .into_values()
+            .map(DeviceOperation::SetBinding)
+            .collect();
+        operations.extend(self.left_handed.take().map(DeviceOperation::SetLeftHanded));
+        operations.extend(self.dpi.take().map(DeviceOperation::SetDpi));
+        operations
+    }
 }
 
 pub struct WorkerModel {
     next_device_id: DeviceId,
-    devices: HashMap<DeviceId, (PathBuf, HpMouse)>, // associate with udev device?
+    devices: HashMap<DeviceId, WorkerDevice>,
 }
 
 impl Model for WorkerModel {
@@ -62,12 +185,116 @@ impl WorkerModel {
             }),
         );
 
-        // XXX errors
-        let _ = mouse.query_firmware().unwrap();
+        self.devices.insert(
+            self.next_device_id.clone(),
+            WorkerDevice {
+                path,
+                mouse,
+                running,
+                writes: 0,
+                queue: WriteQueue::default(),
+            },
+        );
+        self.next_device_id.0 += 1;
 
+        self.run(device_id, DeviceOperation::QueryFirmware, parent_sender);
+    }
+
+    // Perform `operation`, and report failure to `AppModel`
+    fn run(&self, id: DeviceId, operation: DeviceOperation, parent_sender: &Sender<super::AppMsg>) {
+        // Device may have been removed
+        let mouse = match self.devices.get(&id) {
+            Some(device) => &device.mouse,
+            None => return,
+        };
+        let res: io::Result<()> = match &operation {
+            DeviceOperation::QueryFirmware => mouse.query_firmware(),
+            DeviceOperation::QueryState => mouse
+                .query_battery()
+                .and_then(|_| mouse.query_button())
+                .and_then(|_| mouse.query_dpi()),
+            DeviceOperation::QueryBattery => mouse.query_battery(),
+            DeviceOperation::SetDpi(value) => mouse.set_dpi(*value),
+            DeviceOperation::SetLeftHanded(value) => mouse.set_left_handed(*value),
+            DeviceOperation::SetBinding(button) => mouse.set_button(button.clone(), false),
+            DeviceOperation::Verify => {
+                send!(parent_sender, super::AppMsg::VerifyStarted(id.clone()));
+                mouse.query_button().and_then(|_| mouse.query_dpi())
+            }
+        };
+        if let Err(err) = res {
+            let error = DeviceError {
+                operation,
+                message: err.to_string(),
+            };
+            send!(parent_sender, super::AppMsg::DeviceError(id, error));
+        }
+    }
+
+    // Queue a write, and schedule a flush if one isn't already pending
+    fn queue_write(
+        &mut self,
+        id: DeviceId,
+        operation: DeviceOperation,
+        sender: &Sender<WorkerMsg>,
+    ) {
+        let queue = match self.devices.get_mut(&id) {
+            Some(device) => &mut device.queue,
+            None => return,
+        };
+        queue.push(operation);
+        if queue.flush_scheduled {
+            return;
+        }
+        queue.flush_scheduled = true;
+        let delay = queue.last_flush.map_or(Duration::ZERO, |x| {
+            WRITE_INTERVAL.saturating_sub(x.elapsed())
+        });
+        if delay.is_zero() {
+            send!(sender, WorkerMsg::Flush(id));
+        } else {
+            thread::spawn(glib::clone!(@strong sender => move || {
+                thread::sleep(delay);
+                send!(sender, WorkerMsg::Flush(id));
+            }));
+        }
+    }
+
+    // Perform queued writes, then verify them once no more writes follow
+    fn flush(
+        &mut self,
+        id: DeviceId,
+        sender: &Sender<WorkerMsg>,
+        parent_sender: &Sender<super::AppMsg>,
+    ) {
+        let device = match self.devices.get_mut(&id) {
+            Some(device) => device,
+            None => return,
+        };
+        device.queue.flush_scheduled = false;
+        device.queue.last_flush = Some(Instant::now());
+        let operations = device.queue.take();
+        if operations.is_empty() {
+            return;
+        }
+
+        device.writes += 1;
+        let writes = device.writes;
+        thread::spawn(glib::clone!(@strong id, @strong sender => move || {
+            thread::sleep(VERIFY_DELAY);
+            send!(sender, WorkerMsg::Verify(id, writes));
+        }));
+
+        for operation in operations {
+            self.run(id.clone(), operation, parent_sender);
+        }
+    }
+
+    fn device_id_for_path(&self, path: &Path) -> Option<DeviceId> {
         self.devices
-            .insert(self.next_device_id.clone(), (path, mouse));
-        self.next_device_id.0 += 1;
+            .iter()
+            .find(|(_, device)| device.path == path)
+            .map(|(id, _)| id.clone())
     }
 }
 
@@ -88,9 +315,29 @@ impl ComponentUpdate<super::AppModel> for WorkerModel {
     ) {
         match msg {
             WorkerMsg::Disconnect(id) => {
-                self.devices.remove(&id);
-                send!(parent_sender, super::AppMsg::DeviceRemoved(id));
-                eprintln!("End reader");
+                // Reader may exit after device was already removed
+                if let Some(device) = self.devices.remove(&id) {
+                    device.running.store(false, Ordering::SeqCst);
+                    send!(parent_sender, super::AppMsg::DeviceRemoved(id));
+                    eprintln!("End reader");
+                }
+            }
+            WorkerMsg::MonitorError(err) => {
+                send!(parent_sender, super::AppMsg::ShowError(err));
+            }
+            WorkerMsg::MonitorStopped(reason) => {
+                send!(parent_sender, super::AppMsg::DeviceMonitorStopped(reason));
+            }
+            WorkerMsg::RemoveDevice(path) => {
+                if let Some(id) = self.device_id_for_path(&path) {
+                    send!(sender, WorkerMsg::Disconnect(id));
+                }
+            }
+            WorkerMsg::DeviceChanged(path) => {
+                // Query firmware again, which also refreshes the device's state
+                if let Some(id) = self.device_id_for_path(&path) {
+                    self.run(id, DeviceOperation::QueryFirmware, &parent_sender);
+                }
             }
             WorkerMsg::SetDeviceMonitor(device_monitor) => {
                 thread::spawn(glib::clone!(@strong sender => move || {
@@ -98,44 +345,77 @@ impl ComponentUpdate<super::AppModel> for WorkerModel {
                 }));
             }
             WorkerMsg::AddDevice(path, mouse) => {
-                self.add_device(path, mouse, &sender, &parent_sender);
+                // A restarted monitor enumerates devices that are already open
+                if self.device_id_for_path(&path).is_none() {
+                    self.add_device(path, mouse, &sender, &parent_sender);
+                }
             }
             WorkerMsg::HasFirmware(id) => {
-                // XXX errors
-                let mouse = &self.devices.get(&id).unwrap().1;
-                let _ = mouse.query_battery().unwrap();
-                let _ = mouse.query_button().unwrap();
-                let _ = mouse.query_dpi().unwrap();
+                self.run(id, DeviceOperation::QueryState, &parent_sender);
             }
             WorkerMsg::SetDpi(id, value) => {
-                if let Some((_, mouse)) = &self.devices.get(&id) {
-                    // XXX error
-                    let _ = mouse.set_dpi(value);
-                }
+                self.queue_write(id, DeviceOperation::SetDpi(value), &sender);
             }
             WorkerMsg::SetLeftHanded(id, value) => {
-                if let Some((_, mouse)) = &self.devices.get(&id) {
-                    // XXX error
-                    let _ = mouse.set_left_handed(value);
-                }
+                self.queue_write(id, DeviceOperation::SetLeftHanded(value), &sender);
             }
             WorkerMsg::SetBinding(id, button) => {
-                if let Some((_, mouse)) = &self.devices.get(&id) {
-                    // XXX error
-                    let _ = mouse.set_button(button, false);
+                self.queue_write(id, DeviceOperation::SetBinding(button), &sender);
+            }
+            WorkerMsg::Run(id, operation) if operation.is_write() => {
+                self.queue_write(id, operation, &sender);
+            }
+            WorkerMsg::Flush(id) => {
+                self.flush(id, &sender, &parent_sender);
+            }
+            WorkerMsg::Run(id, operation) => {
+                self.run(id, operation, &parent_sender);
+            }
+            WorkerMsg::Verify(id, writes) => {
+                if self.devices.get(&id).map(|x| x.writes) == Some(writes) {
+                    self.run(id, DeviceOperation::Verify, &parent_sender);
                 }
             }
         }
     }
 }
 
-fn device_monitor_thread(device_monitor: DeviceMonitorProcess, sender: Sender<WorkerMsg>) {
-    for i in device_monitor {
-        // XXX error handling?
-        if let Ok((path, mouse)) = i {
-            send!(sender, WorkerMsg::AddDevice(path, mouse));
+fn device_monitor_thread(device_monitor: DeviceMonitor, sender: Sender<WorkerMsg>) {
+    let devices = match device_monitor.devices() {
+        Ok(devices) => devices,
+        Err(err) => {
+            let reason = format!("Failed to monitor devices: {}", err);
+            send!(sender, WorkerMsg::MonitorStopped(reason));
+            return;
+        }
+    };
+    // The iterator ends after an error that stops the monitor
+    let mut last_error = None;
+    for i in devices {
+        if let Some(err) = last_error.take() {
+            send!(sender, WorkerMsg::MonitorError(err));
+        }
+        match i {
+            Ok(DeviceEvent::Added(path, mouse)) => {
+                send!(sender, WorkerMsg::AddDevice(path, mouse));
+            }
+            Ok(DeviceEvent::Removed(path)) => {
+                send!(sender, WorkerMsg::RemoveDevice(path));
+            }
+            Ok(DeviceEvent::Changed(path)) => {
+                send!(sender, WorkerMsg::DeviceChanged(path));
+            }
+            Ok(DeviceEvent::OpenFailed(path, err)) => {
+                let message = format!("Failed to open `{}`: {}", path.display(), err);
+                send!(sender, WorkerMsg::MonitorError(message));
+            }
+            Err(err) => {
+                last_error = Some(err.to_string());
+            }
         }
     }
+    let reason = last_error.unwrap_or_else(|| "Device monitor exited".to_string());
+    send!(sender, WorkerMsg::MonitorStopped(reason));
 }
 
 fn reader_thread(
@@ -172,3 +452,30 @@ fn reader_thread(
 
     send!(sender, WorkerMsg::Disconnect(device_id));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_queue_coalesces() {
+        let binding = |id, action| {
+            DeviceOperation::SetBinding(Button::new_raw(id, 0, PressType::Normal, vec![action]))
+        };
+        let mut queue = WriteQueue::default();
+        queue.push(DeviceOperation::SetDpi(800));
+        queue.push(binding(1, 1));
+        queue.push(binding(0, 1));
+        queue.push(DeviceOperation::SetDpi(1200));
+        queue.push(binding(1, 2));
+
+        let operations = queue.take();
+        assert_eq!(operations.len(), 3);
+        assert!(matches!(&operations[0], DeviceOperation::SetBinding(x) if x.id == 0));
+        assert!(
+            matches!(&operations[1], DeviceOperation::SetBinding(x) if x.id == 1 && x.action() == [2])
+        );
+        assert!(matches!(operations[2], DeviceOperation::SetDpi(1200)));
+        assert!(queue.take().is_empty());
+    }
+}
@@ -0,0 +1,279 @@
+//! Privileged helper, started by the GUI with `pkexec`, that monitors for
+//! devices, opens them and sends them to the GUI.
+//!
+//! It takes no arguments, and only opens device nodes udev reports for
+//! supported mice. Before doing anything else, it switches back to the user
+//! that ran `pkexec`, and only allows the syscalls it needs. It keeps
+//! `CAP_DAC_OVERRIDE` in its permitted set, but only raises it while opening a
+//! `/dev/hidraw*` node.
+
+use nix::{
+    sys::socket::{getsockopt, sockopt, SockType},
+    unistd::{setgroups, setresgid, setresuid, Gid, Uid, User},
+};
+use std::{env, io, path::Path, process};
+
+use mouse_configurator::{
+    monitor_protocol::{self, MonitorMessage},
+    DeviceInfo, HpMouse,
+};
+
+const CAP_DAC_OVERRIDE: u32 = 1;
+// `_LINUX_CAPABILITY_VERSION_3`
+const CAPABILITY_VERSION: u32 = 0x2008_0522;
+
+#[repr(C)]
+struct CapUserHeader {
+    version: u32,
+    pid: libc::c_int,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct CapUserData {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32,
+}
+
+fn check(res: libc::c_int) -> io::Result<()> {
+    if res == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+// Set `CAP_DAC_OVERRIDE` as permitted, and effective if `effective` is set
+fn set_capabilities(effective: bool) -> io::Result<()> {
+    let header = CapUserHeader {
+        version: CAPABILITY_VERSION,
+        pid: 0,
+    };
+    let mut data = [CapUserData::default(); 2];
+    if effective {
+        data[0].effective = 1 << CAP_DAC_OVERRIDE;
+    }
+    data[0].permitted = 1 << CAP_DAC_OVERRIDE;
+    check(unsafe { libc::syscall(libc::SYS_capset, &header, data.as_ptr()) } as libc::c_int)
+}
+
+// Switch to the user that ran `pkexec`, keeping only `CAP_DAC_OVERRIDE`, and
+// only in the permitted set
+fn drop_privileges() -> io::Result<()> {
+    if !Uid::effective().is_root() {
+        return Ok(());
+    }
+
+    let uid = env::var("PKEXEC_UID")
+        .ok()
+        .and_then(|x| x.parse().ok())
+        .map(Uid::from_raw)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "`PKEXEC_UID` not set"))?;
+    if uid.is_root() {
+        return Ok(());
+    }
+    let gid = User::from_uid(uid)?
+        .map(|user| user.gid)
+        .unwrap_or_else(|| Gid::from_raw(uid.as_raw()));
+
+    check(unsafe { libc::prctl(libc::PR_SET_KEEPCAPS, 1, 0, 0, 0) })?;
+    setgroups(&[])?;
+    setresgid(gid, gid, gid)?;
+    setresuid(uid, uid, uid)?;
+    set_capabilities(false)?;
+
+    check(unsafe { libc::prctl(libc::PR_SET_KEEPCAPS, 0, 0, 0, 0) })
+}
+
+fn is_hidraw_node(path: &Path) -> bool {
+    let name = match path.strip_prefix("/dev").ok().and_then(|x| x.to_str()) {
+        Some(name) => name,
+        None => return false,
+    };
+    match name.strip_prefix("hidraw") {
+        Some(number) => !number.is_empty() && number.bytes().all(|x| x.is_ascii_digit()),
+        None => false,
+    }
+}
+
+// Open the device, with `CAP_DAC_OVERRIDE` raised only for the `open()`
+fn open_device(device_info: &DeviceInfo) -> io::Result<HpMouse> {
+    if !is_hidraw_node(&device_info.devnode) {
+        return Err(io::Error::from_raw_os_error(libc::EPERM));
+    }
+    // Fails if not started by `pkexec`, and opening as the user may still work
+    let raised = set_capabilities(true).is_ok();
+    let res = device_info.open();
+    if raised {
+        set_capabilities(false)?;
+    }
+    res
+}
+
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+fn restrict_syscalls() -> io::Result<()> {
+    #[cfg(target_arch = "x86_64")]
+    const AUDIT_ARCH: u32 = 0xc000_003e;
+    #[cfg(target_arch = "aarch64")]
+    const AUDIT_ARCH: u32 = 0xc000_00b7;
+
+    // Syscalls used by udev, the allocator, and the socket to the GUI.
+    // Anything else fails with `ENOSYS`, which libc treats as the syscall
+    // being unavailable, rather than killing the helper.
+    const ALLOWED: &[libc::c_long] = &[
+        // Memory
+        libc::SYS_brk,
+        libc::SYS_mmap,
+        libc::SYS_munmap,
+        libc::SYS_mremap,
+        libc::SYS_mprotect,
+        libc::SYS_madvise,
+        // Files, for sysfs and udev's database
+        libc::SYS_openat,
+        libc::SYS_close,
+        libc::SYS_read,
+        libc::SYS_readv,
+        libc::SYS_write,
+        libc::SYS_writev,
+        libc::SYS_pread64,
+        libc::SYS_lseek,
+        libc::SYS_fstat,
+        libc::SYS_newfstatat,
+        libc::SYS_statx,
+        libc::SYS_readlinkat,
+        libc::SYS_getdents64,
+        libc::SYS_faccessat,
+        libc::SYS_faccessat2,
+        libc::SYS_fcntl,
+        libc::SYS_ioctl,
+        // Udev monitor, and the socket to the GUI
+        libc::SYS_socket,
+        libc::SYS_bind,
+        libc::SYS_setsockopt,
+        libc::SYS_getsockopt,
+        libc::SYS_getsockname,
+        libc::SYS_sendmsg,
+        libc::SYS_sendto,
+        libc::SYS_recvmsg,
+        libc::SYS_recvfrom,
+        libc::SYS_ppoll,
+        // Raising `CAP_DAC_OVERRIDE`, which can't add other capabilities
+        libc::SYS_capget,
+        libc::SYS_capset,
+        // Process
+        libc::SYS_rt_sigreturn,
+        libc::SYS_rt_sigaction,
+        libc::SYS_rt_sigprocmask,
+        libc::SYS_sigaltstack,
+        libc::SYS_futex,
+        libc::SYS_sched_yield,
+        libc::SYS_getrandom,
+        libc::SYS_clock_gettime,
+        libc::SYS_clock_nanosleep,
+        libc::SYS_nanosleep,
+        libc::SYS_gettimeofday,
+        libc::SYS_getpid,
+        libc::SYS_gettid,
+        libc::SYS_getuid,
+        libc::SYS_geteuid,
+        libc::SYS_getgid,
+        libc::SYS_getegid,
+        libc::SYS_uname,
+        libc::SYS_exit,
+        libc::SYS_exit_group,
+        // Older variants, that don't exist on aarch64
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_open,
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_stat,
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_lstat,
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_access,
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_readlink,
+        #[cfg(target_arch = "x86_64")]
+        libc::SYS_poll,
+    ];
+
+    const BPF_LD_W_ABS: u16 = 0x20;
+    const BPF_JMP_JEQ_K: u16 = 0x15;
+    const BPF_JMP_JGE_K: u16 = 0x35;
+    const BPF_RET_K: u16 = 0x06;
+    const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+    // Set in syscall numbers of the x32 ABI, which shares `AUDIT_ARCH` with
+    // x86_64
+    const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+    // Offsets in `seccomp_data`
+    const NR: u32 = 0;
+    const ARCH: u32 = 4;
+
+    let stmt = |code, k| libc::sock_filter {
+        code,
+        jt: 0,
+        jf: 0,
+        k,
+    };
+    let jump = |code, k, jt, jf| libc::sock_filter { code, jt, jf, k };
+
+    let mut filter = vec![
+        stmt(BPF_LD_W_ABS, ARCH),
+        jump(BPF_JMP_JEQ_K, AUDIT_ARCH, 1, 0),
+        stmt(BPF_RET_K, SECCOMP_RET_KILL_PROCESS),
+        stmt(BPF_LD_W_ABS, NR),
+        jump(BPF_JMP_JGE_K, X32_SYSCALL_BIT, 0, 1),
+        stmt(BPF_RET_K, SECCOMP_RET_KILL_PROCESS),
+    ];
+    for (i, nr) in ALLOWED.iter().enumerate() {
+        // Jump to the `SECCOMP_RET_ALLOW` at the end
+        let jt = (ALLOWED.len() - i) as u8;
+        filter.push(jump(BPF_JMP_JEQ_K, *nr as u32, jt, 0));
+    }
+    filter.push(stmt(
+        BPF_RET_K,
+        libc::SECCOMP_RET_ERRNO | libc::ENOSYS as u32,
+    ));
+    filter.push(stmt(BPF_RET_K, libc::SECCOMP_RET_ALLOW));
+
+    let prog = libc::sock_fprog {
+        len: filter.len() as libc::c_ushort,
+        filter: filter.as_mut_ptr(),
+    };
+    check(unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) })?;
+    check(unsafe {
+        libc::prctl(
+            libc::PR_SET_SECCOMP,
+            libc::SECCOMP_MODE_FILTER,
+            &prog as *const libc::sock_fprog,
+        )
+    })
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn restrict_syscalls() -> io::Result<()> {
+    check(unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) })
+}
+
+fn main() {
+    if env::args_os().len() > 1 {
+        eprintln!("mouse-configurator-helper takes no arguments");
+        process::exit(2);
+    }
+
+    // Only serve the socket the GUI passes as stdin
+    let sock = libc::STDIN_FILENO;
+    if getsockopt(sock, sockopt::SockType) != Ok(SockType::SeqPacket) {
+        eprintln!("mouse-configurator-helper must be started by mouse-configurator");
+        process::exit(2);
+    }
+
+    if let Err(err) = drop_privileges().and_then(|_| restrict_syscalls()) {
+        let reason = format!("Failed to drop privileges: {}", err);
+        eprintln!("{}", reason);
+        let _ = monitor_protocol::send_message(sock, &MonitorMessage::Shutdown { reason }, None);
+        process::exit(1);
+    }
+
+    monitor_protocol::serve_with(sock, open_device);
+}
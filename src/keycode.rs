@@ -0,0 +1,967 @@
+//! HID usages used in `Op::Key` and `Op::Media` payloads.
+//!
+//! Matches /sys/kernel/debug/hid/*/rdesc: key payloads are the modifier byte
+//! followed by Keyboard/Keypad page (0x07) usages, and media payloads are
+//! Consumer page (0x0C) usages.
+
+use std::{fmt, ops, str::FromStr};
+
+use crate::Value;
+
+macro_rules! usages {
+    (
+        $(#[$meta:meta])*
+        pub enum $name:ident: $repr:ty {
+            $($variant:ident = $value:literal => $label:literal,)*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        #[repr($repr)]
+        pub enum $name {
+            $($variant = $value,)*
+        }
+
+        impl $name {
+            pub const ALL: &'static [Self] = &[$(Self::$variant,)*];
+
+            pub fn from_usage(usage: $repr) -> Option<Self> {
+                match usage {
+                    $($value => Some(Self::$variant),)*
+                    _ => None,
+                }
+            }
+
+            pub fn usage(self) -> $repr {
+                self as $repr
+            }
+
+            /// Identifier, as used in configuration and on the command line
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Self::$variant => stringify!($variant),)*
+                }
+            }
+
+            /// Human readable label
+            pub fn label(self) -> &'static str {
+                match self {
+                    $(Self::$variant => $label,)*
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.label())
+            }
+        }
+
+        // Accepts either the name or the label, ignoring case
+        impl FromStr for $name {
+            type Err = String;
+
+            fn from_str(s: &str) -> Result<Self, String> {
+                Self::ALL
+                    .iter()
+                    .copied()
+                    .find(|x| x.name().eq_ignore_ascii_case(s))
+                    .or_else(|| {
+                        Self::ALL
+                            .iter()
+                            .copied()
+                            .find(|x| x.label().eq_ignore_ascii_case(s))
+                    })
+                    .ok_or_else(|| format!("Unrecognized {}: `{}`", stringify!($name), s))
+            }
+        }
+    };
+}
+
+usages! {
+    /// Keyboard/Keypad page
+    pub enum Key: u8 {
+        A = 0x04 => "A",
+        B = 0x05 => "B",
+        C = 0x06 => "C",
+        D = 0x07 => "D",
+        E = 0x08 => "E",
+        F = 0x09 => "F",
+        G = 0x0A => "G",
+        H = 0x0B => "H",
+        I = 0x0C => "I",
+        J = 0x0D => "J",
+        K = 0x0E => "K",
+        L = 0x0F => "L",
+        M = 0x10 => "M",
+        N = 0x11 => "N",
+        O = 0x12 => "O",
+        P = 0x13 => "P",
+        Q = 0x14 => "Q",
+        R = 0x15 => "R",
+        S = 0x16 => "S",
+        T = 0x17 => "T",
+        U = 0x18 => "U",
+        V = 0x19 => "V",
+        W = 0x1A => "W",
+        X = 0x1B => "X",
+        Y = 0x1C => "Y",
+        Z = 0x1D => "Z",
+        Digit1 = 0x1E => "1",
+        Digit2 = 0x1F => "2",
+        Digit3 = 0x20 => "3",
+        Digit4 = 0x21 => "4",
+        Digit5 = 0x22 => "5",
+        Digit6 = 0x23 => "6",
+        Digit7 = 0x24 => "7",
+        Digit8 = 0x25 => "8",
+        Digit9 = 0x26 => "9",
+        Digit0 = 0x27 => "0",
+        Enter = 0x28 => "Enter",
+        Esc = 0x29 => "Escape",
+        Backspace = 0x2A => "Backspace",
+        Tab = 0x2B => "Tab",
+        Space = 0x2C => "Space",
+        Minus = 0x2D => "-",
+        Equal = 0x2E => "=",
+        LeftBrace = 0x2F => "[",
+        RightBrace = 0x30 => "]",
+        Backslash = 0x31 => "\\",
+        HashTilde = 0x32 => "#",
+        Semicolon = 0x33 => ";",
+        Apostrophe = 0x34 => "'",
+        Grave = 0x35 => "`",
+        Comma = 0x36 => ",",
+        Dot = 0x37 => ".",
+        Slash = 0x38 => "/",
+        CapsLock = 0x39 => "Caps Lock",
+        F1 = 0x3A => "F1",
+        F2 = 0x3B => "F2",
+        F3 = 0x3C => "F3",
+        F4 = 0x3D => "F4",
+        F5 = 0x3E => "F5",
+        F6 = 0x3F => "F6",
+        F7 = 0x40 => "F7",
+        F8 = 0x41 => "F8",
+        F9 = 0x42 => "F9",
+        F10 = 0x43 => "F10",
+        F11 = 0x44 => "F11",
+        F12 = 0x45 => "F12",
+        SysRq = 0x46 => "Print Screen",
+        ScrollLock = 0x47 => "Scroll Lock",
+        Pause = 0x48 => "Pause",
+        Insert = 0x49 => "Insert",
+        Home = 0x4A => "Home",
+        PageUp = 0x4B => "Page Up",
+        Delete = 0x4C => "Delete",
+        End = 0x4D => "End",
+        PageDown = 0x4E => "Page Down",
+        Right = 0x4F => "Right",
+        Left = 0x50 => "Left",
+        Down = 0x51 => "Down",
+        Up = 0x52 => "Up",
+        NumLock = 0x53 => "Num Lock",
+        KpSlash = 0x54 => "Keypad /",
+        KpAsterisk = 0x55 => "Keypad *",
+        KpMinus = 0x56 => "Keypad -",
+        KpPlus = 0x57 => "Keypad +",
+        KpEnter = 0x58 => "Keypad Enter",
+        Kp1 = 0x59 => "Keypad 1",
+        Kp2 = 0x5A => "Keypad 2",
+        Kp3 = 0x5B => "Keypad 3",
+        Kp4 = 0x5C => "Keypad 4",
+        Kp5 = 0x5D => "Keypad 5",
+        Kp6 = 0x5E => "Keypad 6",
+        Kp7 = 0x5F => "Keypad 7",
+        Kp8 = 0x60 => "Keypad 8",
+        Kp9 = 0x61 => "Keypad 9",
+        Kp0 = 0x62 => "Keypad 0",
+        KpDot = 0x63 => "Keypad .",
+        Key102nd = 0x64 => "\\ (ISO)",
+        Compose = 0x65 => "Menu",
+        Power = 0x66 => "Power",
+        KpEqual = 0x67 => "Keypad =",
+        F13 = 0x68 => "F13",
+        F14 = 0x69 => "F14",
+        F15 = 0x6A => "F15",
+        F16 = 0x6B => "F16",
+        F17 = 0x6C => "F17",
+        F18 = 0x6D => "F18",
+        F19 = 0x6E => "F19",
+        F20 = 0x6F => "F20",
+        F21 = 0x70 => "F21",
+        F22 = 0x71 => "F22",
+        F23 = 0x72 => "F23",
+        F24 = 0x73 => "F24",
+        Open = 0x74 => "Execute",
+        Help = 0x75 => "Help",
+        Props = 0x76 => "Properties",
+        Front = 0x77 => "Select",
+        Stop = 0x78 => "Stop",
+        Again = 0x79 => "Again",
+        Undo = 0x7A => "Undo",
+        Cut = 0x7B => "Cut",
+        Copy = 0x7C => "Copy",
+        Paste = 0x7D => "Paste",
+        Find = 0x7E => "Find",
+        Mute = 0x7F => "Mute",
+        VolumeUp = 0x80 => "Volume Up",
+        VolumeDown = 0x81 => "Volume Down",
+        LockingCapsLock = 0x82 => "Locking Caps Lock",
+        LockingNumLock = 0x83 => "Locking Num Lock",
+        LockingScrollLock = 0x84 => "Locking Scroll Lock",
+        KpComma = 0x85 => "Keypad ,",
+        KpEqualSign = 0x86 => "Keypad = (AS/400)",
+        Ro = 0x87 => "Ro",
+        KatakanaHiragana = 0x88 => "Katakana/Hiragana",
+        Yen = 0x89 => "Yen",
+        Henkan = 0x8A => "Henkan",
+        Muhenkan = 0x8B => "Muhenkan",
+        KpJpComma = 0x8C => "Keypad JP Comma",
+        International7 = 0x8D => "International 7",
+        International8 = 0x8E => "International 8",
+        International9 = 0x8F => "International 9",
+        Hangeul = 0x90 => "Hangeul",
+        Hanja = 0x91 => "Hanja",
+        Katakana = 0x92 => "Katakana",
+        Hiragana = 0x93 => "Hiragana",
+        ZenkakuHankaku = 0x94 => "Zenkaku/Hankaku",
+        Lang6 = 0x95 => "Language 6",
+        Lang7 = 0x96 => "Language 7",
+        Lang8 = 0x97 => "Language 8",
+        Lang9 = 0x98 => "Language 9",
+        AltErase = 0x99 => "Alternate Erase",
+        Attention = 0x9A => "SysReq/Attention",
+        Cancel = 0x9B => "Cancel",
+        Clear = 0x9C => "Clear",
+        Prior = 0x9D => "Prior",
+        Return = 0x9E => "Return",
+        Separator = 0x9F => "Separator",
+        Out = 0xA0 => "Out",
+        Oper = 0xA1 => "Oper",
+        ClearAgain = 0xA2 => "Clear/Again",
+        CrSel = 0xA3 => "CrSel/Props",
+        ExSel = 0xA4 => "ExSel",
+        Kp00 = 0xB0 => "Keypad 00",
+        Kp000 = 0xB1 => "Keypad 000",
+        ThousandsSeparator = 0xB2 => "Thousands Separator",
+        DecimalSeparator = 0xB3 => "Decimal Separator",
+        CurrencyUnit = 0xB4 => "Currency Unit",
+        CurrencySubunit = 0xB5 => "Currency Sub-unit",
+        KpLeftParen = 0xB6 => "Keypad (",
+        KpRightParen = 0xB7 => "Keypad )",
+        KpLeftBrace = 0xB8 => "Keypad {",
+        KpRightBrace = 0xB9 => "Keypad }",
+        KpTab = 0xBA => "Keypad Tab",
+        KpBackspace = 0xBB => "Keypad Backspace",
+        KpA = 0xBC => "Keypad A",
+        KpB = 0xBD => "Keypad B",
+        KpC = 0xBE => "Keypad C",
+        KpD = 0xBF => "Keypad D",
+        KpE = 0xC0 => "Keypad E",
+        KpF = 0xC1 => "Keypad F",
+        KpXor = 0xC2 => "Keypad XOR",
+        KpCaret = 0xC3 => "Keypad ^",
+        KpPercent = 0xC4 => "Keypad %",
+        KpLess = 0xC5 => "Keypad <",
+        KpGreater = 0xC6 => "Keypad >",
+        KpAmpersand = 0xC7 => "Keypad &",
+        KpDoubleAmpersand = 0xC8 => "Keypad &&",
+        KpBar = 0xC9 => "Keypad |",
+        KpDoubleBar = 0xCA => "Keypad ||",
+        KpColon = 0xCB => "Keypad :",
+        KpHash = 0xCC => "Keypad #",
+        KpSpace = 0xCD => "Keypad Space",
+        KpAt = 0xCE => "Keypad @",
+        KpExclamation = 0xCF => "Keypad !",
+        KpMemoryStore = 0xD0 => "Keypad Memory Store",
+        KpMemoryRecall = 0xD1 => "Keypad Memory Recall",
+        KpMemoryClear = 0xD2 => "Keypad Memory Clear",
+        KpMemoryAdd = 0xD3 => "Keypad Memory Add",
+        KpMemorySubtract = 0xD4 => "Keypad Memory Subtract",
+        KpMemoryMultiply = 0xD5 => "Keypad Memory Multiply",
+        KpMemoryDivide = 0xD6 => "Keypad Memory Divide",
+        KpPlusMinus = 0xD7 => "Keypad +/-",
+        KpClear = 0xD8 => "Keypad Clear",
+        KpClearEntry = 0xD9 => "Keypad Clear Entry",
+        KpBinary = 0xDA => "Keypad Binary",
+        KpOctal = 0xDB => "Keypad Octal",
+        KpDecimal = 0xDC => "Keypad Decimal",
+        KpHexadecimal = 0xDD => "Keypad Hexadecimal",
+        LeftCtrl = 0xE0 => "Left Ctrl",
+        LeftShift = 0xE1 => "Left Shift",
+        LeftAlt = 0xE2 => "Left Alt",
+        LeftSuper = 0xE3 => "Super",
+        RightCtrl = 0xE4 => "Right Ctrl",
+        RightShift = 0xE5 => "Right Shift",
+        RightAlt = 0xE6 => "Right Alt",
+        RightSuper = 0xE7 => "Right Super",
+    }
+}
+
+usages! {
+    /// Consumer page. Only named controls are included; collection usages
+    /// are omitted.
+    pub enum Consumer: u16 {
+        Plus10 = 0x20 => "+10",
+        Plus100 = 0x21 => "+100",
+        AmPm = 0x22 => "AM/PM",
+        Power = 0x30 => "Power",
+        Reset = 0x31 => "Reset",
+        Sleep = 0x32 => "Sleep",
+        SleepAfter = 0x33 => "Sleep After",
+        SleepMode = 0x34 => "Sleep Mode",
+        Illumination = 0x35 => "Illumination",
+        Menu = 0x40 => "Menu",
+        MenuPick = 0x41 => "Menu Pick",
+        MenuUp = 0x42 => "Menu Up",
+        MenuDown = 0x43 => "Menu Down",
+        MenuLeft = 0x44 => "Menu Left",
+        MenuRight = 0x45 => "Menu Right",
+        MenuEscape = 0x46 => "Menu Escape",
+        MenuValueIncrease = 0x47 => "Menu Value Increase",
+        MenuValueDecrease = 0x48 => "Menu Value Decrease",
+        DataOnScreen = 0x60 => "Data On Screen",
+        ClosedCaption = 0x61 => "Closed Caption",
+        ClosedCaptionSelect = 0x62 => "Closed Caption Select",
+        VcrTv = 0x63 => "VCR/TV",
+        BroadcastMode = 0x64 => "Broadcast Mode",
+        Snapshot = 0x65 => "Snapshot",
+        Still = 0x66 => "Still",
+        PictureInPictureToggle = 0x67 => "Picture-in-Picture Toggle",
+        PictureInPictureSwap = 0x68 => "Picture-in-Picture Swap",
+        RedMenuButton = 0x69 => "Red Menu Button",
+        GreenMenuButton = 0x6A => "Green Menu Button",
+        BlueMenuButton = 0x6B => "Blue Menu Button",
+        YellowMenuButton = 0x6C => "Yellow Menu Button",
+        Aspect = 0x6D => "Aspect",
+        ThreeDModeSelect = 0x6E => "3D Mode Select",
+        BrightnessUp = 0x6F => "Display Brightness Up",
+        BrightnessDown = 0x70 => "Display Brightness Down",
+        Brightness = 0x71 => "Display Brightness",
+        BacklightToggle = 0x72 => "Display Backlight Toggle",
+        BrightnessMinimum = 0x73 => "Display Brightness Minimum",
+        BrightnessMaximum = 0x74 => "Display Brightness Maximum",
+        BrightnessAuto = 0x75 => "Display Brightness Auto",
+        CameraAccessEnabled = 0x76 => "Camera Access Enabled",
+        CameraAccessDisabled = 0x77 => "Camera Access Disabled",
+        CameraAccessToggle = 0x78 => "Camera Access Toggle",
+        KeyboardBrightnessUp = 0x79 => "Keyboard Brightness Up",
+        KeyboardBrightnessDown = 0x7A => "Keyboard Brightness Down",
+        KeyboardBacklightSetLevel = 0x7B => "Keyboard Backlight Set Level",
+        KeyboardBacklightToggle = 0x7C => "Keyboard Backlight Toggle",
+        KeyboardBacklightMinimum = 0x7D => "Keyboard Backlight Minimum",
+        KeyboardBacklightMaximum = 0x7E => "Keyboard Backlight Maximum",
+        KeyboardBacklightAuto = 0x7F => "Keyboard Backlight Auto",
+        Selection = 0x80 => "Selection",
+        AssignSelection = 0x81 => "Assign Selection",
+        ModeStep = 0x82 => "Mode Step",
+        RecallLast = 0x83 => "Recall Last",
+        EnterChannel = 0x84 => "Enter Channel",
+        OrderMovie = 0x85 => "Order Movie",
+        Channel = 0x86 => "Channel",
+        MediaSelection = 0x87 => "Media Selection",
+        MediaSelectComputer = 0x88 => "Media Select Computer",
+        MediaSelectTv = 0x89 => "Media Select TV",
+        MediaSelectWww = 0x8A => "Media Select WWW",
+        MediaSelectDvd = 0x8B => "Media Select DVD",
+        MediaSelectTelephone = 0x8C => "Media Select Telephone",
+        MediaSelectProgramGuide = 0x8D => "Media Select Program Guide",
+        MediaSelectVideoPhone = 0x8E => "Media Select Video Phone",
+        MediaSelectGames = 0x8F => "Media Select Games",
+        MediaSelectMessages = 0x90 => "Media Select Messages",
+        MediaSelectCd = 0x91 => "Media Select CD",
+        MediaSelectVcr = 0x92 => "Media Select VCR",
+        MediaSelectTuner = 0x93 => "Media Select Tuner",
+        Quit = 0x94 => "Quit",
+        Help = 0x95 => "Help",
+        MediaSelectTape = 0x96 => "Media Select Tape",
+        MediaSelectCable = 0x97 => "Media Select Cable",
+        MediaSelectSatellite = 0x98 => "Media Select Satellite",
+        MediaSelectSecurity = 0x99 => "Media Select Security",
+        MediaSelectHome = 0x9A => "Media Select Home",
+        MediaSelectCall = 0x9B => "Media Select Call",
+        ChannelIncrement = 0x9C => "Channel Increment",
+        ChannelDecrement = 0x9D => "Channel Decrement",
+        MediaSelectSap = 0x9E => "Media Select SAP",
+        VcrPlus = 0xA0 => "VCR Plus",
+        Once = 0xA1 => "Once",
+        Daily = 0xA2 => "Daily",
+        Weekly = 0xA3 => "Weekly",
+        Monthly = 0xA4 => "Monthly",
+        Play = 0xB0 => "Play",
+        Pause = 0xB1 => "Pause",
+        Record = 0xB2 => "Record",
+        FastForward = 0xB3 => "Fast Forward",
+        Rewind = 0xB4 => "Rewind",
+        NextSong = 0xB5 => "Next Track",
+        PreviousSong = 0xB6 => "Previous Track",
+        StopPlayback = 0xB7 => "Stop",
+        Eject = 0xB8 => "Eject",
+        RandomPlay = 0xB9 => "Random Play",
+        SelectDisc = 0xBA => "Select Disc",
+        EnterDisc = 0xBB => "Enter Disc",
+        Repeat = 0xBC => "Repeat",
+        Tracking = 0xBD => "Tracking",
+        TrackNormal = 0xBE => "Track Normal",
+        SlowTracking = 0xBF => "Slow Tracking",
+        FrameForward = 0xC0 => "Frame Forward",
+        FrameBack = 0xC1 => "Frame Back",
+        Mark = 0xC2 => "Mark",
+        ClearMark = 0xC3 => "Clear Mark",
+        RepeatFromMark = 0xC4 => "Repeat From Mark",
+        ReturnToMark = 0xC5 => "Return To Mark",
+        SearchMarkForward = 0xC6 => "Search Mark Forward",
+        SearchMarkBackwards = 0xC7 => "Search Mark Backwards",
+        CounterReset = 0xC8 => "Counter Reset",
+        ShowCounter = 0xC9 => "Show Counter",
+        TrackingIncrement = 0xCA => "Tracking Increment",
+        TrackingDecrement = 0xCB => "Tracking Decrement",
+        StopEject = 0xCC => "Stop/Eject",
+        PlayPause = 0xCD => "Play/Pause",
+        PlaySkip = 0xCE => "Play/Skip",
+        VoiceCommand = 0xCF => "Voice Command",
+        InvokeCaptureInterface = 0xD0 => "Invoke Capture Interface",
+        GameRecording = 0xD1 => "Start or Stop Game Recording",
+        HistoricalGameCapture = 0xD2 => "Historical Game Capture",
+        GameScreenshot = 0xD3 => "Capture Game Screenshot",
+        RecordingIndicator = 0xD4 => "Show or Hide Recording Indicator",
+        MicrophoneCapture = 0xD5 => "Start or Stop Microphone Capture",
+        CameraCapture = 0xD6 => "Start or Stop Camera Capture",
+        GameBroadcast = 0xD7 => "Start or Stop Game Broadcast",
+        VoiceDictation = 0xD8 => "Start or Stop Voice Dictation",
+        EmojiPicker = 0xD9 => "Emoji Picker",
+        Volume = 0xE0 => "Volume",
+        Balance = 0xE1 => "Balance",
+        Mute = 0xE2 => "Mute",
+        Bass = 0xE3 => "Bass",
+        Treble = 0xE4 => "Treble",
+        BassBoost = 0xE5 => "Bass Boost",
+        SurroundMode = 0xE6 => "Surround Mode",
+        Loudness = 0xE7 => "Loudness",
+        Mpx = 0xE8 => "MPX",
+        VolumeUp = 0xE9 => "Volume Up",
+        VolumeDown = 0xEA => "Volume Down",
+        SpeedSelect = 0xF0 => "Speed Select",
+        PlaybackSpeed = 0xF1 => "Playback Speed",
+        StandardPlay = 0xF2 => "Standard Play",
+        LongPlay = 0xF3 => "Long Play",
+        ExtendedPlay = 0xF4 => "Extended Play",
+        Slow = 0xF5 => "Slow",
+        FanEnable = 0x100 => "Fan Enable",
+        FanSpeed = 0x101 => "Fan Speed",
+        LightEnable = 0x102 => "Light Enable",
+        LightIlluminationLevel = 0x103 => "Light Illumination Level",
+        ClimateControlEnable = 0x104 => "Climate Control Enable",
+        RoomTemperature = 0x105 => "Room Temperature",
+        SecurityEnable = 0x106 => "Security Enable",
+        FireAlarm = 0x107 => "Fire Alarm",
+        PoliceAlarm = 0x108 => "Police Alarm",
+        Proximity = 0x109 => "Proximity",
+        Motion = 0x10A => "Motion",
+        DuressAlarm = 0x10B => "Duress Alarm",
+        HoldupAlarm = 0x10C => "Holdup Alarm",
+        MedicalAlarm = 0x10D => "Medical Alarm",
+        BalanceRight = 0x150 => "Balance Right",
+        BalanceLeft = 0x151 => "Balance Left",
+        BassIncrement = 0x152 => "Bass Increment",
+        BassDecrement = 0x153 => "Bass Decrement",
+        TrebleIncrement = 0x154 => "Treble Increment",
+        TrebleDecrement = 0x155 => "Treble Decrement",
+        SpeakerSystem = 0x160 => "Speaker System",
+        ChannelLeft = 0x161 => "Channel Left",
+        ChannelRight = 0x162 => "Channel Right",
+        ChannelCenter = 0x163 => "Channel Center",
+        ChannelFront = 0x164 => "Channel Front",
+        ChannelCenterFront = 0x165 => "Channel Center Front",
+        ChannelSide = 0x166 => "Channel Side",
+        ChannelSurround = 0x167 => "Channel Surround",
+        ChannelLowFrequencyEnhancement = 0x168 => "Channel Low Frequency Enhancement",
+        ChannelTop = 0x169 => "Channel Top",
+        ChannelUnknown = 0x16A => "Channel Unknown",
+        SubChannel = 0x170 => "Sub-channel",
+        SubChannelIncrement = 0x171 => "Sub-channel Increment",
+        SubChannelDecrement = 0x172 => "Sub-channel Decrement",
+        AlternateAudioIncrement = 0x173 => "Alternate Audio Increment",
+        AlternateAudioDecrement = 0x174 => "Alternate Audio Decrement",
+        AlLaunchButtonConfigurationTool = 0x181 => "Launch Button Configuration Tool",
+        AlProgrammableButtonConfiguration = 0x182 => "Programmable Button Configuration",
+        AlConsumerControlConfiguration = 0x183 => "Consumer Control Configuration",
+        AlWordProcessor = 0x184 => "Word Processor",
+        AlTextEditor = 0x185 => "Text Editor",
+        AlSpreadsheet = 0x186 => "Spreadsheet",
+        AlGraphicsEditor = 0x187 => "Graphics Editor",
+        AlPresentationApp = 0x188 => "Presentation App",
+        AlDatabaseApp = 0x189 => "Database App",
+        AlEmailReader = 0x18A => "Email Reader",
+        AlNewsreader = 0x18B => "Newsreader",
+        AlVoicemail = 0x18C => "Voicemail",
+        AlContacts = 0x18D => "Contacts",
+        AlCalendar = 0x18E => "Calendar",
+        AlTaskManager = 0x18F => "Task/Project Manager",
+        AlJournal = 0x190 => "Log/Journal/Timecard",
+        AlFinance = 0x191 => "Checkbook/Finance",
+        AlCalculator = 0x192 => "Calculator",
+        AlAvCapturePlayback = 0x193 => "A/V Capture/Playback",
+        AlLocalMachineBrowser = 0x194 => "Local Machine Browser",
+        AlLanWanBrowser = 0x195 => "LAN/WAN Browser",
+        AlInternetBrowser = 0x196 => "Internet Browser",
+        AlRemoteNetworking = 0x197 => "Remote Networking",
+        AlNetworkConference = 0x198 => "Network Conference",
+        AlNetworkChat = 0x199 => "Network Chat",
+        AlTelephony = 0x19A => "Telephony/Dialer",
+        AlLogon = 0x19B => "Logon",
+        AlLogoff = 0x19C => "Logoff",
+        AlLogonLogoff = 0x19D => "Logon/Logoff",
+        AlTerminalLock = 0x19E => "Terminal Lock/Screensaver",
+        AlControlPanel = 0x19F => "Control Panel",
+        AlCommandLineProcessor = 0x1A0 => "Command Line Processor/Run",
+        AlProcessManager = 0x1A1 => "Process/Task Manager",
+        AlSelectTask = 0x1A2 => "Select Task/Application",
+        AlNextTask = 0x1A3 => "Next Task/Application",
+        AlPreviousTask = 0x1A4 => "Previous Task/Application",
+        AlPreemptiveHaltTask = 0x1A5 => "Preemptive Halt Task/Application",
+        AlIntegratedHelpCenter = 0x1A6 => "Integrated Help Center",
+        AlDocuments = 0x1A7 => "Documents",
+        AlThesaurus = 0x1A8 => "Thesaurus",
+        AlDictionary = 0x1A9 => "Dictionary",
+        AlDesktop = 0x1AA => "Desktop",
+        AlSpellCheck = 0x1AB => "Spell Check",
+        AlGrammarCheck = 0x1AC => "Grammar Check",
+        AlWirelessStatus = 0x1AD => "Wireless Status",
+        AlKeyboardLayout = 0x1AE => "Keyboard Layout",
+        AlVirusProtection = 0x1AF => "Virus Protection",
+        AlEncryption = 0x1B0 => "Encryption",
+        AlScreenSaver = 0x1B1 => "Screen Saver",
+        AlAlarms = 0x1B2 => "Alarms",
+        AlClock = 0x1B3 => "Clock",
+        AlFileBrowser = 0x1B4 => "File Browser",
+        AlPowerStatus = 0x1B5 => "Power Status",
+        AlImageBrowser = 0x1B6 => "Image Browser",
+        AlAudioBrowser = 0x1B7 => "Audio Browser",
+        AlMovieBrowser = 0x1B8 => "Movie Browser",
+        AlDigitalRightsManager = 0x1B9 => "Digital Rights Manager",
+        AlDigitalWallet = 0x1BA => "Digital Wallet",
+        AlInstantMessaging = 0x1BC => "Instant Messaging",
+        AlOemFeatures = 0x1BD => "OEM Features/Tips/Tutorial Browser",
+        AlOemHelp = 0x1BE => "OEM Help",
+        AlOnlineCommunity = 0x1BF => "Online Community",
+        AlEntertainmentContentBrowser = 0x1C0 => "Entertainment Content Browser",
+        AlOnlineShoppingBrowser = 0x1C1 => "Online Shopping Browser",
+        AlSmartCardInformation = 0x1C2 => "SmartCard Information/Help",
+        AlMarketMonitor = 0x1C3 => "Market Monitor/Finance Browser",
+        AlCustomizedCorporateNews = 0x1C4 => "Customized Corporate News Browser",
+        AlOnlineActivityBrowser = 0x1C5 => "Online Activity Browser",
+        AlSearchBrowser = 0x1C6 => "Research/Search Browser",
+        AlAudioPlayer = 0x1C7 => "Audio Player",
+        AlMessageStatus = 0x1C8 => "Message Status",
+        AlContactSync = 0x1C9 => "Contact Sync",
+        AlNavigation = 0x1CA => "Navigation",
+        AlDesktopAssistant = 0x1CB => "Context-aware Desktop Assistant",
+        AcNew = 0x201 => "New",
+        AcOpen = 0x202 => "Open",
+        AcClose = 0x203 => "Close",
+        AcExit = 0x204 => "Exit",
+        AcMaximize = 0x205 => "Maximize",
+        AcMinimize = 0x206 => "Minimize",
+        AcSave = 0x207 => "Save",
+        AcPrint = 0x208 => "Print",
+        AcProperties = 0x209 => "Properties",
+        AcUndo = 0x21A => "Undo",
+        AcCopy = 0x21B => "Copy",
+        AcCut = 0x21C => "Cut",
+        AcPaste = 0x21D => "Paste",
+        AcSelectAll = 0x21E => "Select All",
+        AcFind = 0x21F => "Find",
+        AcFindAndReplace = 0x220 => "Find and Replace",
+        AcSearch = 0x221 => "Search",
+        AcGoTo = 0x222 => "Go To",
+        AcHome = 0x223 => "Home",
+        AcBack = 0x224 => "Back",
+        AcForward = 0x225 => "Forward",
+        AcStop = 0x226 => "Stop Loading",
+        AcRefresh = 0x227 => "Refresh",
+        AcPreviousLink = 0x228 => "Previous Link",
+        AcNextLink = 0x229 => "Next Link",
+        AcBookmarks = 0x22A => "Bookmarks",
+        AcHistory = 0x22B => "History",
+        AcSubscriptions = 0x22C => "Subscriptions",
+        AcZoomIn = 0x22D => "Zoom In",
+        AcZoomOut = 0x22E => "Zoom Out",
+        AcZoom = 0x22F => "Zoom",
+        AcFullScreenView = 0x230 => "Full Screen View",
+        AcNormalView = 0x231 => "Normal View",
+        AcViewToggle = 0x232 => "View Toggle",
+        AcScrollUp = 0x233 => "Scroll Up",
+        AcScrollDown = 0x234 => "Scroll Down",
+        AcScroll = 0x235 => "Scroll",
+        AcPanLeft = 0x236 => "Pan Left",
+        AcPanRight = 0x237 => "Pan Right",
+        AcPan = 0x238 => "Pan",
+        AcNewWindow = 0x239 => "New Window",
+        AcTileHorizontally = 0x23A => "Tile Horizontally",
+        AcTileVertically = 0x23B => "Tile Vertically",
+        AcFormat = 0x23C => "Format",
+        AcEdit = 0x23D => "Edit",
+        AcBold = 0x23E => "Bold",
+        AcItalics = 0x23F => "Italics",
+        AcUnderline = 0x240 => "Underline",
+        AcStrikethrough = 0x241 => "Strikethrough",
+        AcSubscript = 0x242 => "Subscript",
+        AcSuperscript = 0x243 => "Superscript",
+        AcAllCaps = 0x244 => "All Caps",
+        AcRotate = 0x245 => "Rotate",
+        AcResize = 0x246 => "Resize",
+        AcFlipHorizontal = 0x247 => "Flip Horizontal",
+        AcFlipVertical = 0x248 => "Flip Vertical",
+        AcMirrorHorizontal = 0x249 => "Mirror Horizontal",
+        AcMirrorVertical = 0x24A => "Mirror Vertical",
+        AcFontSelect = 0x24B => "Font Select",
+        AcFontColor = 0x24C => "Font Color",
+        AcFontSize = 0x24D => "Font Size",
+        AcJustifyLeft = 0x24E => "Justify Left",
+        AcJustifyCenterH = 0x24F => "Justify Center Horizontally",
+        AcJustifyRight = 0x250 => "Justify Right",
+        AcJustifyBlockH = 0x251 => "Justify Block Horizontally",
+        AcJustifyTop = 0x252 => "Justify Top",
+        AcJustifyCenterV = 0x253 => "Justify Center Vertically",
+        AcJustifyBottom = 0x254 => "Justify Bottom",
+        AcJustifyBlockV = 0x255 => "Justify Block Vertically",
+        AcIndentDecrease = 0x256 => "Decrease Indent",
+        AcIndentIncrease = 0x257 => "Increase Indent",
+        AcNumberedList = 0x258 => "Numbered List",
+        AcRestartNumbering = 0x259 => "Restart Numbering",
+        AcBulletedList = 0x25A => "Bulleted List",
+        AcPromote = 0x25B => "Promote",
+        AcDemote = 0x25C => "Demote",
+        AcYes = 0x25D => "Yes",
+        AcNo = 0x25E => "No",
+        AcCancel = 0x25F => "Cancel",
+        AcCatalog = 0x260 => "Catalog",
+        AcBuyCheckout = 0x261 => "Buy/Checkout",
+        AcAddToCart = 0x262 => "Add to Cart",
+        AcExpand = 0x263 => "Expand",
+        AcExpandAll = 0x264 => "Expand All",
+        AcCollapse = 0x265 => "Collapse",
+        AcCollapseAll = 0x266 => "Collapse All",
+        AcPrintPreview = 0x267 => "Print Preview",
+        AcPasteSpecial = 0x268 => "Paste Special",
+        AcInsertMode = 0x269 => "Insert Mode",
+        AcDelete = 0x26A => "Delete",
+        AcLock = 0x26B => "Lock",
+        AcUnlock = 0x26C => "Unlock",
+        AcProtect = 0x26D => "Protect",
+        AcUnprotect = 0x26E => "Unprotect",
+        AcAttachComment = 0x26F => "Attach Comment",
+        AcDeleteComment = 0x270 => "Delete Comment",
+        AcViewComment = 0x271 => "View Comment",
+        AcSelectWord = 0x272 => "Select Word",
+        AcSelectSentence = 0x273 => "Select Sentence",
+        AcSelectParagraph = 0x274 => "Select Paragraph",
+        AcSelectColumn = 0x275 => "Select Column",
+        AcSelectRow = 0x276 => "Select Row",
+        AcSelectTable = 0x277 => "Select Table",
+        AcSelectObject = 0x278 => "Select Object",
+        AcRedo = 0x279 => "Redo/Repeat",
+        AcSort = 0x27A => "Sort",
+        AcSortAscending = 0x27B => "Sort Ascending",
+        AcSortDescending = 0x27C => "Sort Descending",
+        AcFilter = 0x27D => "Filter",
+        AcSetClock = 0x27E => "Set Clock",
+        AcViewClock = 0x27F => "View Clock",
+        AcSelectTimeZone = 0x280 => "Select Time Zone",
+        AcEditTimeZones = 0x281 => "Edit Time Zones",
+        AcSetAlarm = 0x282 => "Set Alarm",
+        AcClearAlarm = 0x283 => "Clear Alarm",
+        AcSnoozeAlarm = 0x284 => "Snooze Alarm",
+        AcResetAlarm = 0x285 => "Reset Alarm",
+        AcSynchronize = 0x286 => "Synchronize",
+        AcSendReceive = 0x287 => "Send/Receive",
+        AcSendTo = 0x288 => "Send To",
+        AcReply = 0x289 => "Reply",
+        AcReplyAll = 0x28A => "Reply All",
+        AcForwardMsg = 0x28B => "Forward Message",
+        AcSend = 0x28C => "Send",
+        AcAttachFile = 0x28D => "Attach File",
+        AcUpload = 0x28E => "Upload",
+        AcDownload = 0x28F => "Download",
+        AcSetBorders = 0x290 => "Set Borders",
+        AcInsertRow = 0x291 => "Insert Row",
+        AcInsertColumn = 0x292 => "Insert Column",
+        AcInsertFile = 0x293 => "Insert File",
+        AcInsertPicture = 0x294 => "Insert Picture",
+        AcInsertObject = 0x295 => "Insert Object",
+        AcInsertSymbol = 0x296 => "Insert Symbol",
+        AcSaveAndClose = 0x297 => "Save and Close",
+        AcRename = 0x298 => "Rename",
+        AcMerge = 0x299 => "Merge",
+        AcSplit = 0x29A => "Split",
+        AcDistributeHorizontally = 0x29B => "Distribute Horizontally",
+        AcDistributeVertically = 0x29C => "Distribute Vertically",
+        AcNextKeyboardLayout = 0x29D => "Next Keyboard Layout",
+        AcNavigationGuidance = 0x29E => "Navigation Guidance",
+        AcDesktopShowAllWindows = 0x29F => "Show All Windows",
+        AcSoftKeyLeft = 0x2A0 => "Soft Key Left",
+        AcSoftKeyRight = 0x2A1 => "Soft Key Right",
+        AcDesktopShowAllApplications = 0x2A2 => "Show All Applications",
+        AcIdleKeepAlive = 0x2B0 => "Idle Keep Alive",
+    }
+}
+
+impl Key {
+    /// Keys are sent as one byte in the payload
+    pub fn payload(self) -> Value<i8> {
+        Value::Const(self as i8)
+    }
+}
+
+impl Consumer {
+    /// The firmware carries one byte per usage in media payloads, so only
+    /// usages below 0x100 can be sent.
+    pub fn payload(self) -> Option<Value<i8>> {
+        u8::try_from(self as u16)
+            .ok()
+            .map(|x| Value::Const(x as i8))
+    }
+}
+
+/// Modifier byte at the start of a key payload
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers(u8);
+
+impl Modifiers {
+    pub const CTRL: Self = Self(1 << 0);
+    pub const SHIFT: Self = Self(1 << 1);
+    pub const ALT: Self = Self(1 << 2);
+    pub const SUPER: Self = Self(1 << 3);
+    pub const RIGHT_CTRL: Self = Self(1 << 4);
+    pub const RIGHT_SHIFT: Self = Self(1 << 5);
+    pub const RIGHT_ALT: Self = Self(1 << 6);
+    pub const RIGHT_SUPER: Self = Self(1 << 7);
+
+    // Name, label, and aliases accepted when parsing
+    const NAMES: &'static [(Self, &'static str, &'static [&'static str])] = &[
+        (Self::CTRL, "Ctrl", &["Control", "LeftCtrl"]),
+        (Self::SHIFT, "Shift", &["LeftShift"]),
+        (Self::ALT, "Alt", &["LeftAlt"]),
+        (Self::SUPER, "Super", &["LeftSuper", "Meta", "Win", "Logo"]),
+        (
+            Self::RIGHT_CTRL,
+            "Right Ctrl",
+            &["RightCtrl", "RightControl"],
+        ),
+        (Self::RIGHT_SHIFT, "Right Shift", &["RightShift"]),
+        (Self::RIGHT_ALT, "Right Alt", &["RightAlt", "AltGr"]),
+        (Self::RIGHT_SUPER, "Right Super", &["RightSuper"]),
+    ];
+
+    pub fn empty() -> Self {
+        Self(0)
+    }
+
+    pub fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Labels of set modifiers, in conventional order
+    pub fn labels(self) -> impl Iterator<Item = &'static str> {
+        Self::NAMES
+            .iter()
+            .filter(move |(modifier, _, _)| self.contains(*modifier))
+            .map(|(_, label, _)| *label)
+    }
+
+    pub fn payload(self) -> Value<i8> {
+        Value::Const(self.0 as i8)
+    }
+}
+
+impl ops::BitOr for Modifiers {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl ops::BitOrAssign for Modifiers {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl FromStr for Modifiers {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        Self::NAMES
+            .iter()
+            .find(|(_, label, aliases)| {
+                label.eq_ignore_ascii_case(s) || aliases.iter().any(|x| x.eq_ignore_ascii_case(s))
+            })
+            .map(|(modifier, _, _)| *modifier)
+            .ok_or_else(|| format!("Unrecognized modifier: `{}`", s))
+    }
+}
+
+/// Modifiers and keys pressed together, like "Ctrl+Shift+A"
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct KeyCombination {
+    pub modifiers: Modifiers,
+    pub keys: Vec<Key>,
+}
+
+impl KeyCombination {
+    pub fn new(modifiers: Modifiers, key: Key) -> Self {
+        Self {
+            modifiers,
+            keys: vec![key],
+        }
+    }
+
+    /// Parse a key payload. Fails if it contains variables or unknown usages.
+    pub fn from_payload(payload: &[Value<i8>]) -> Option<Self> {
+        let (modifiers, keys) = payload.split_first()?;
+        let modifiers = match modifiers {
+            Value::Const(modifiers) => Modifiers::from_bits(*modifiers as u8),
+            Value::Var(_) => return None,
+        };
+        let keys = keys
+            .iter()
+            .map(|key| match key {
+                Value::Const(key) => Key::from_usage(*key as u8),
+                Value::Var(_) => None,
+            })
+            .collect::<Option<_>>()?;
+        Some(Self { modifiers, keys })
+    }
+
+    pub fn payload(&self) -> Vec<Value<i8>> {
+        let mut payload = vec![self.modifiers.payload()];
+        payload.extend(self.keys.iter().map(|key| key.payload()));
+        payload
+    }
+}
+
+impl fmt::Display for KeyCombination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let labels = self
+            .modifiers
+            .labels()
+            .chain(self.keys.iter().map(|key| key.label()));
+        for (i, label) in labels.enumerate() {
+            if i != 0 {
+                f.write_str("+")?;
+            }
+            f.write_str(label)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for KeyCombination {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let mut combination = Self::default();
+        if s.trim().is_empty() {
+            return Err("Empty key combination".to_string());
+        }
+        // Split on `+`, but join a part with the following ones if it isn't
+        // recognized alone, for labels like "Keypad +"
+        let mut parts = s.split('+');
+        while let Some(first) = parts.next() {
+            let mut part = first.to_string();
+            loop {
+                if let Ok(modifier) = part.trim().parse::<Modifiers>() {
+                    combination.modifiers |= modifier;
+                    break;
+                }
+                match part.trim().parse::<Key>() {
+                    Ok(key) => {
+                        combination.keys.push(key);
+                        break;
+                    }
+                    Err(err) => match parts.next() {
+                        Some(next) => {
+                            part.push('+');
+                            part.push_str(next);
+                        }
+                        // Report the part that couldn't be parsed alone
+                        None if part == first => return Err(err),
+                        None => return Err(format!("Unrecognized Key: `{}`", first.trim())),
+                    },
+                }
+            }
+        }
+        if combination.modifiers.is_empty() && combination.keys.is_empty() {
+            return Err("Empty key combination".to_string());
+        }
+        Ok(combination)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usage_round_trip() {
+        for key in Key::ALL {
+            assert_eq!(Key::from_usage(key.usage()), Some(*key));
+            assert_eq!(key.name().parse::<Key>(), Ok(*key));
+        }
+        for usage in Consumer::ALL {
+            assert_eq!(Consumer::from_usage(usage.usage()), Some(*usage));
+            assert_eq!(usage.name().parse::<Consumer>(), Ok(*usage));
+        }
+    }
+
+    #[test]
+    fn parse_key_combination() {
+        let combination = "ctrl+Shift+a".parse::<KeyCombination>().unwrap();
+        assert_eq!(
+            combination,
+            KeyCombination::new(Modifiers::CTRL | Modifiers::SHIFT, Key::A)
+        );
+        assert_eq!(combination.to_string(), "Ctrl+Shift+A");
+        assert_eq!(
+            KeyCombination::from_payload(&combination.payload()),
+            Some(combination)
+        );
+        assert_eq!(
+            "Super+/".parse::<KeyCombination>(),
+            Ok(KeyCombination::new(Modifiers::SUPER, Key::Slash))
+        );
+        assert!("Ctrl+Nope".parse::<KeyCombination>().is_err());
+
+        // Labels containing `+` round trip
+        for key in [Key::KpPlus, Key::KpPlusMinus] {
+            let combination = KeyCombination::new(Modifiers::CTRL, key);
+            assert_eq!(
+                combination.to_string().parse::<KeyCombination>(),
+                Ok(combination)
+            );
+        }
+        // Non-ASCII input is an error, rather than a panic
+        assert_eq!(
+            "é+A".parse::<KeyCombination>(),
+            Err("Unrecognized Key: `é`".to_string())
+        );
+        assert!("Ctrl++".parse::<KeyCombination>().is_err());
+        assert!("Ctrl+".parse::<KeyCombination>().is_err());
+        assert!(" ".parse::<KeyCombination>().is_err());
+    }
+}
@@ -5,14 +5,23 @@ use std::{
     sync::Arc,
 };
 
+pub mod battery_history;
+pub mod bindings;
 pub mod button;
 pub use button::{Button, Op, PressType, Value};
+pub mod config;
 mod enumerate;
-pub use enumerate::{enumerate, monitor, DeviceInfo};
+pub use enumerate::{enumerate, monitor, DeviceInfo, MonitorEvent};
 mod event;
 pub use event::{Event, HpMouseEvents, ReadRes};
 mod hid;
 use hid::Hid;
+pub mod keycode;
+pub mod monitor_protocol;
+pub mod policy;
+pub mod presets;
+pub mod profile_library;
+pub mod shortcut;
 
 const HP_SIGNATURE: u16 = 0xCF3;
 
@@ -0,0 +1,229 @@
+//! Protocol used by the privileged device monitor to send opened devices to
+//! the GUI, over a `SOCK_SEQPACKET` socket.
+//!
+//! Each packet is a JSON `MonitorMessage`. The first is always `Hello`, so a
+//! monitor from a different version of the program is detected.
+
+use nix::{
+    cmsg_space,
+    errno::Errno,
+    sys::socket::{recvmsg, sendmsg, ControlMessage, ControlMessageOwned, MsgFlags, UnixAddr},
+};
+use std::{
+    io::{self, IoSlice, IoSliceMut},
+    os::unix::io::{AsRawFd, RawFd},
+    path::PathBuf,
+    slice,
+};
+
+use crate::{enumerate, monitor, DeviceInfo, HpMouse, MonitorEvent};
+
+// Increment on any incompatible change to `MonitorMessage`
+pub const PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum MonitorMessage {
+    Hello { version: u32 },
+    // Sent with the opened device as `SCM_RIGHTS`
+    DeviceAdded { path: PathBuf },
+    DeviceRemoved { path: PathBuf },
+    DeviceChanged { path: PathBuf },
+    OpenFailed { path: PathBuf, errno: i32 },
+    // Monitor is exiting, due to an error
+    Shutdown { reason: String },
+}
+
+pub fn send_message(sock: RawFd, message: &MonitorMessage, fd: Option<RawFd>) -> io::Result<()> {
+    let data = serde_json::to_vec(message)?;
+    let iov = &[IoSlice::new(&data)];
+    let fds = match &fd {
+        Some(fd) => slice::from_ref(fd),
+        None => &[],
+    };
+    let cmsgs = &[ControlMessage::ScmRights(fds)];
+    let cmsgs = if fd.is_some() { &cmsgs[..] } else { &[] };
+    loop {
+        match sendmsg(sock, iov, cmsgs, MsgFlags::empty(), None::<&UnixAddr>) {
+            Ok(_) => return Ok(()),
+            Err(Errno::EINTR) => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+// Returns `None` if the other end of the socket is closed
+pub fn recv_message(
+    sock: RawFd,
+    buf: &mut [u8],
+) -> io::Result<Option<(MonitorMessage, Option<RawFd>)>> {
+    let mut iov = [IoSliceMut::new(buf)];
+    let mut cmsg = cmsg_space!(RawFd);
+    let (bytes, fds) = loop {
+        match recvmsg::<UnixAddr>(sock, &mut iov, Some(&mut cmsg), MsgFlags::empty()) {
+            Ok(msg) => {
+                let mut fds = Vec::new();
+                for cmsg in msg.cmsgs() {
+                    if let ControlMessageOwned::ScmRights(x) = cmsg {
+                        fds.extend_from_slice(&x);
+                    }
+                }
+                break (msg.bytes, fds);
+            }
+            Err(Errno::EINTR) => {}
+            Err(err) => return Err(err.into()),
+        }
+    };
+    if bytes == 0 {
+        return Ok(None);
+    }
+    let invalid = |msg: String| {
+        for fd in &fds {
+            let _ = nix::unistd::close(*fd);
+        }
+        io::Error::new(io::ErrorKind::InvalidData, msg)
+    };
+    let message: MonitorMessage = serde_json::from_slice(&iov[0][..bytes])
+        .map_err(|err| invalid(format!("Invalid message from device monitor: {}", err)))?;
+    let expects_fd = matches!(message, MonitorMessage::DeviceAdded { .. });
+    match fds.as_slice() {
+        [] if !expects_fd => Ok(Some((message, None))),
+        [fd] if expects_fd => Ok(Some((message, Some(*fd)))),
+        _ => Err(invalid(format!(
+            "Unexpected file descriptors with message from device monitor: {:?}",
+            message
+        ))),
+    }
+}
+
+/// Send devices over `sock` as they are connected, until the other end closes
+/// the socket. Runs in the privileged helper.
+pub fn serve(sock: RawFd) {
+    serve_with(sock, DeviceInfo::open)
+}
+
+/// Like `serve`, but opening devices with `open`
+pub fn serve_with(sock: RawFd, open: impl Fn(&DeviceInfo) -> io::Result<HpMouse>) {
+    let send = |message: &MonitorMessage, fd: Option<RawFd>| {
+        let res = send_message(sock, message, fd);
+        if let Err(err) = &res {
+            eprintln!("Error writing to socket: {}", err);
+        }
+        res
+    };
+
+    if send(
+        &MonitorMessage::Hello {
+            version: PROTOCOL_VERSION,
+        },
+        None,
+    )
+    .is_err()
+    {
+        return;
+    }
+
+    let res = monitor().and_then(|monitor_events| {
+        let current_devices = enumerate()?;
+        Ok((current_devices, monitor_events))
+    });
+    let (current_devices, monitor_events) = match res {
+        Ok(res) => res,
+        Err(err) => {
+            let _ = send(
+                &MonitorMessage::Shutdown {
+                    reason: err.to_string(),
+                },
+                None,
+            );
+            return;
+        }
+    };
+
+    let events = current_devices
+        .into_iter()
+        .map(MonitorEvent::Added)
+        .chain(monitor_events);
+    for event in events {
+        let res = match event {
+            MonitorEvent::Added(device_info) => match open(&device_info) {
+                Ok(device) => send(
+                    &MonitorMessage::DeviceAdded {
+                        path: device_info.devnode,
+                    },
+                    Some(device.as_raw_fd()),
+                ),
+                Err(err) => send(
+                    &MonitorMessage::OpenFailed {
+                        path: device_info.devnode,
+                        errno: err.raw_os_error().unwrap_or(0),
+                    },
+                    None,
+                ),
+            },
+            MonitorEvent::Removed(path) => send(&MonitorMessage::DeviceRemoved { path }, None),
+            MonitorEvent::Changed(device_info) => send(
+                &MonitorMessage::DeviceChanged {
+                    path: device_info.devnode,
+                },
+                None,
+            ),
+        };
+        // Parent process exited
+        if res.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nix::sys::socket::{socketpair, AddressFamily, SockFlag, SockType};
+    use std::fs::File;
+
+    use super::*;
+
+    #[test]
+    fn message_round_trip() {
+        let (sock1, sock2) = socketpair(
+            AddressFamily::Unix,
+            SockType::SeqPacket,
+            None,
+            SockFlag::SOCK_CLOEXEC,
+        )
+        .unwrap();
+        let mut buf = vec![0; 4096];
+
+        let message = MonitorMessage::OpenFailed {
+            path: "/dev/hidraw3".into(),
+            errno: libc::EACCES,
+        };
+        send_message(sock1, &message, None).unwrap();
+        assert_eq!(
+            recv_message(sock2, &mut buf).unwrap(),
+            Some((message, None))
+        );
+
+        let file = File::open("/dev/null").unwrap();
+        let message = MonitorMessage::DeviceAdded {
+            path: "/dev/hidraw3".into(),
+        };
+        send_message(sock1, &message, Some(file.as_raw_fd())).unwrap();
+        let (received, fd) = recv_message(sock2, &mut buf).unwrap().unwrap();
+        assert_eq!(received, message);
+        nix::unistd::close(fd.unwrap()).unwrap();
+
+        // A device must be sent with `DeviceAdded`
+        send_message(
+            sock1,
+            &MonitorMessage::Hello { version: 1 },
+            Some(file.as_raw_fd()),
+        )
+        .unwrap();
+        assert!(recv_message(sock2, &mut buf).is_err());
+
+        nix::unistd::close(sock1).unwrap();
+        assert!(recv_message(sock2, &mut buf).unwrap().is_none());
+        nix::unistd::close(sock2).unwrap();
+    }
+}
@@ -0,0 +1,284 @@
+//! Settings locked by an administrator, for managed deployments.
+//!
+//! The policy is read from `POLICY_PATH`, and every field is optional:
+//!
+//! ```json
+//! {
+//!     "left-handed": false,
+//!     "min-dpi": 800,
+//!     "max-dpi": 1600,
+//!     "disabled-buttons": ["left-center"],
+//!     "allowed-presets": ["right-click", "middle-click", "back", "forward"]
+//! }
+//! ```
+//!
+//! Locked values are applied to the profile sent to the mouse and shown,
+//! leaving saved configs unchanged, so removing the policy restores the user's
+//! own settings. Changes that conflict with the policy are refused. If
+//! `allowed-presets` is set, custom shortcuts aren't allowed either. A button
+//! that is disabled, or has a binding that isn't allowed, is bound to the
+//! `disabled` preset.
+//!
+//! If the policy file exists but can't be read, every setting is locked, so
+//! a mistake in the policy doesn't unlock anything.
+
+use once_cell::sync::Lazy;
+use std::{borrow::Cow, fs, io, path::Path};
+
+use crate::{
+    bindings::{HardwareButton, PresetBinding},
+    config::{round_dpi, Binding, Profile},
+};
+
+pub const POLICY_PATH: &str = "/etc/mouse-configurator/policy.json";
+
+#[derive(Default, serde::Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+struct PolicyFile {
+    left_handed: Option<bool>,
+    min_dpi: Option<f64>,
+    max_dpi: Option<f64>,
+    // Names, like `HardwareButton::from_str` accepts
+    disabled_buttons: Vec<String>,
+    allowed_presets: Option<Vec<PresetBinding>>,
+}
+
+#[derive(Debug, Default)]
+pub struct Policy {
+    pub left_handed: Option<bool>,
+    pub min_dpi: Option<f64>,
+    pub max_dpi: Option<f64>,
+    pub disabled_buttons: Vec<HardwareButton>,
+    /// Presets buttons may be bound to, or `None` to allow any binding
+    pub allowed_presets: Option<Vec<PresetBinding>>,
+    // Set if the policy couldn't be loaded, to lock every setting
+    locked: bool,
+}
+
+static POLICY: Lazy<Result<Policy, String>> = Lazy::new(|| Policy::load(Path::new(POLICY_PATH)));
+
+static LOCKED: Policy = Policy {
+    left_handed: None,
+    min_dpi: None,
+    max_dpi: None,
+    disabled_buttons: Vec::new(),
+    allowed_presets: None,
+    locked: true,
+};
+
+/// The system policy, loaded on first use
+pub fn policy() -> &'static Policy {
+    POLICY.as_ref().unwrap_or(&LOCKED)
+}
+
+/// Error loading the system policy, if any
+pub fn policy_error() -> Option<&'static str> {
+    POLICY.as_ref().err().map(String::as_str)
+}
+
+impl Policy {
+    /// Load the policy at `path`. A missing file is an empty policy.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let data = match fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(format!("Failed to read `{}`: {}", path.display(), err)),
+        };
+        Self::parse(&data).map_err(|err| format!("Invalid policy `{}`: {}", path.display(), err))
+    }
+
+    fn parse(data: &str) -> Result<Self, String> {
+        let file: PolicyFile = serde_json::from_str(data).map_err(|x| x.to_string())?;
+        if let (Some(min), Some(max)) = (file.min_dpi, file.max_dpi) {
+            if min > max {
+                return Err("`min-dpi` is greater than `max-dpi`".to_string());
+            }
+        }
+        let disabled_buttons = file
+            .disabled_buttons
+            .iter()
+            .map(|x| x.parse())
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            left_handed: file.left_handed,
+            min_dpi: file.min_dpi,
+            max_dpi: file.max_dpi,
+            disabled_buttons,
+            allowed_presets: file.allowed_presets,
+            locked: false,
+        })
+    }
+
+    pub fn left_handed_locked(&self) -> bool {
+        self.locked || self.left_handed.is_some()
+    }
+
+    pub fn dpi_locked(&self) -> bool {
+        self.locked || (self.min_dpi.is_some() && self.min_dpi == self.max_dpi)
+    }
+
+    /// Restrict the range `min..=max` supported by the device to the policy
+    pub fn dpi_range(&self, min: f64, max: f64) -> (f64, f64) {
+        let min = self.min_dpi.map_or(min, |x| x.clamp(min, max));
+        let max = self.max_dpi.map_or(max, |x| x.clamp(min, max));
+        (min, max)
+    }
+
+    pub fn button_locked(&self, button: HardwareButton) -> bool {
+        self.locked || self.disabled_buttons.contains(&button)
+    }
+
+    /// Whether buttons may be bound to custom shortcuts
+    pub fn shortcuts_allowed(&self) -> bool {
+        !self.locked && self.allowed_presets.is_none()
+    }
+
+    pub fn binding_allowed(&self, binding: &Binding) -> bool {
+        match (&self.allowed_presets, binding) {
+            (_, Binding::Preset(PresetBinding::Disabled)) => true,
+            (None, _) => !self.locked,
+            (Some(allowed), Binding::Preset(id)) => allowed.contains(id),
+            (Some(_), _) => false,
+        }
+    }
+
+    pub fn check_left_handed(&self, left_handed: bool) -> Result<(), String> {
+        if self.locked || self.left_handed.filter(|x| *x != left_handed).is_some() {
+            Err("Left handed mode is locked by the administrator".to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn check_dpi(&self, dpi: f64) -> Result<(), String> {
+        if self.locked {
+            return Err("DPI is locked by the administrator".to_string());
+        }
+        if let Some(min) = self.min_dpi.filter(|x| dpi < *x) {
+            return Err(format!(
+                "DPI must be at least {}, set by the administrator",
+                round_dpi(min)
+            ));
+        }
+        if let Some(max) = self.max_dpi.filter(|x| dpi > *x) {
+            return Err(format!(
+                "DPI must be at most {}, set by the administrator",
+                round_dpi(max)
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn check_binding(&self, button: HardwareButton, binding: &Binding) -> Result<(), String> {
+        if self.button_locked(button) {
+            Err(format!(
+                "Button {} is locked by the administrator",
+                button.name()
+            ))
+        } else if !self.binding_allowed(binding) {
+            Err(format!(
+                "Binding `{}` isn't allowed by the administrator",
+                binding.label()
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// `profile`, with locked values merged into it
+    pub fn apply<'a>(&self, profile: &'a Profile) -> Cow<'a, Profile> {
+        let mut enforced = profile.clone();
+        if self.enforce(&mut enforced) {
+            Cow::Owned(enforced)
+        } else {
+            Cow::Borrowed(profile)
+        }
+    }
+
+    // Merge locked values into `profile`. Returns `true` if it changed.
+    fn enforce(&self, profile: &mut Profile) -> bool {
+        // Locked since the policy couldn't be read, so there are no values
+        if self.locked {
+            return false;
+        }
+        let mut changed = false;
+        if let Some(left_handed) = self.left_handed {
+            changed |= profile.left_handed != left_handed;
+            profile.left_handed = left_handed;
+        }
+        let dpi = profile.dpi.clamp(
+            self.min_dpi.unwrap_or(f64::MIN),
+            self.max_dpi.unwrap_or(f64::MAX),
+        );
+        changed |= profile.dpi != dpi;
+        profile.dpi = dpi;
+
+        let disabled = Binding::Preset(PresetBinding::Disabled);
+        for id in HardwareButton::iter() {
+            let default = Binding::Preset(id.def_binding().id);
+            let binding = profile.bindings.get(&id).unwrap_or(&default);
+            if !self.button_locked(id) && self.binding_allowed(binding) {
+                continue;
+            }
+            if *binding != disabled {
+                profile.bindings.insert(id, disabled.clone());
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enforce_policy() {
+        let policy = Policy::parse(
+            r#"{
+                "left-handed": true,
+                "max-dpi": 1600,
+                "disabled-buttons": ["left-center"],
+                "allowed-presets": ["right-click", "middle-click", "back", "forward", "copy"]
+            }"#,
+        )
+        .unwrap();
+        assert!(Policy::parse(r#"{"max-dpi": 1600, "lefthanded": true}"#).is_err());
+
+        let mut profile = Profile {
+            dpi: 2400.,
+            ..Profile::default()
+        };
+        profile.bindings.insert(
+            HardwareButton::LeftTop,
+            Binding::Preset(PresetBinding::Copy),
+        );
+        profile.bindings.insert(
+            HardwareButton::ScrollLeft,
+            Binding::parse("Ctrl+Shift+T").unwrap(),
+        );
+        let profile = policy.apply(&profile).into_owned();
+        assert!(profile.left_handed);
+        assert_eq!(profile.dpi, 1600.);
+        let disabled = Binding::Preset(PresetBinding::Disabled);
+        assert!(profile.bindings[&HardwareButton::LeftCenter] == disabled);
+        assert!(profile.bindings[&HardwareButton::ScrollLeft] == disabled);
+        assert!(profile.bindings[&HardwareButton::LeftTop] == Binding::Preset(PresetBinding::Copy));
+        assert!(matches!(policy.apply(&profile), Cow::Borrowed(_)));
+
+        assert!(policy.check_dpi(1600.).is_ok());
+        assert!(policy.check_dpi(2000.).is_err());
+        assert!(policy.check_left_handed(false).is_err());
+        assert!(policy
+            .check_binding(HardwareButton::Middle, &Binding::parse("Ctrl+C").unwrap())
+            .is_ok());
+        assert!(policy
+            .check_binding(
+                HardwareButton::LeftCenter,
+                &Binding::parse("Ctrl+C").unwrap()
+            )
+            .is_err());
+        assert_eq!(policy.dpi_range(800., 3000.), (800., 1600.));
+    }
+}
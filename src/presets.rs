@@ -0,0 +1,222 @@
+//! Binding presets and profile templates installed as files, so they can be
+//! added without changing the built-in `BINDINGS`.
+//!
+//! Files are searched for in the `presets` and `templates` subdirectories of
+//! the app data directory, then of `org.pop_os.mouseconfigurator` in each of
+//! `XDG_DATA_DIRS`, like `/usr/share/org.pop_os.mouseconfigurator/presets`. A
+//! file in an earlier directory hides one with the same name in a later one.
+//!
+//! A preset file is a category of bindings:
+//!
+//! ```json
+//! {
+//!     "label": "Photo Editing",
+//!     "presets": [
+//!         { "label": "Zoom to Fit", "binding": "Ctrl+0" },
+//!         { "label": "Undo", "binding": "undo" }
+//!     ]
+//! }
+//! ```
+//!
+//! Each binding is a built-in preset name or a key combination, as accepted
+//! by `Binding::parse`. Templates use the format of `ProfileFile`.
+
+use once_cell::sync::Lazy;
+use std::{
+    collections::HashSet,
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::config::{app_data_dir, Binding};
+
+const DEFAULT_DATA_DIRS: &str = "/usr/local/share:/usr/share";
+
+pub struct PresetCategory {
+    pub label: String,
+    pub presets: Vec<Preset>,
+}
+
+pub struct Preset {
+    pub label: String,
+    pub binding: Binding,
+}
+
+#[derive(serde::Deserialize)]
+struct PresetFile {
+    label: String,
+    presets: Vec<PresetEntry>,
+}
+
+#[derive(serde::Deserialize)]
+struct PresetEntry {
+    label: String,
+    binding: String,
+}
+
+/// Categories of presets from files, loaded on first use
+pub static PRESETS: Lazy<Vec<PresetCategory>> = Lazy::new(load_presets);
+
+/// Directories to search for files in `subdir`, in order of priority
+pub fn data_dirs(subdir: &str) -> Vec<PathBuf> {
+    let system_dirs = env::var("XDG_DATA_DIRS").ok();
+    data_dirs_in(&app_data_dir(), system_dirs.as_deref(), subdir)
+}
+
+// Directories for `subdir` in the app data directory `user_dir`, then in each
+// directory of a `XDG_DATA_DIRS` style list
+fn data_dirs_in(user_dir: &Path, system_dirs: Option<&str>, subdir: &str) -> Vec<PathBuf> {
+    let mut dirs = vec![user_dir.join(subdir)];
+    let system_dirs = system_dirs
+        .filter(|x| !x.is_empty())
+        .unwrap_or(DEFAULT_DATA_DIRS);
+    for dir in system_dirs.split(':').filter(|x| !x.is_empty()) {
+        let mut path = PathBuf::from(dir);
+        path.push("org.pop_os.mouseconfigurator");
+        path.push(subdir);
+        dirs.push(path);
+    }
+    dirs
+}
+
+/// `.json` files in the data directories for `subdir`, sorted by name within
+/// each directory, and skipping names found in an earlier directory
+pub fn data_files(subdir: &str) -> Vec<PathBuf> {
+    files_in(&data_dirs(subdir))
+}
+
+fn files_in(dirs: &[PathBuf]) -> Vec<PathBuf> {
+    let mut names = HashSet::new();
+    let mut files = Vec::new();
+    for dir in dirs {
+        let mut dir_files: Vec<PathBuf> = match fs::read_dir(dir) {
+            Ok(entries) => entries.flatten().map(|x| x.path()).collect(),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => {
+                eprintln!("Failed to read `{}`: {}", dir.display(), err);
+                continue;
+            }
+        };
+        dir_files.sort();
+        for path in dir_files {
+            if path.extension().and_then(|x| x.to_str()) == Some("json")
+                && names.insert(path.file_name().unwrap().to_owned())
+            {
+                files.push(path);
+            }
+        }
+    }
+    files
+}
+
+fn load_preset_file(path: &Path) -> Result<PresetCategory, String> {
+    let data = fs::read_to_string(path).map_err(|x| x.to_string())?;
+    let file: PresetFile = serde_json::from_str(&data).map_err(|x| x.to_string())?;
+    let presets = file
+        .presets
+        .into_iter()
+        .map(|entry| {
+            Ok(Preset {
+                binding: Binding::parse(&entry.binding)?,
+                label: entry.label,
+            })
+        })
+        .collect::<Result<_, String>>()?;
+    Ok(PresetCategory {
+        label: file.label,
+        presets,
+    })
+}
+
+fn load_presets() -> Vec<PresetCategory> {
+    load_preset_files(&data_files("presets"))
+}
+
+// Categories from each file that can be loaded
+fn load_preset_files(files: &[PathBuf]) -> Vec<PresetCategory> {
+    files
+        .iter()
+        .filter_map(|path| match load_preset_file(path) {
+            Ok(category) => Some(category),
+            Err(err) => {
+                eprintln!("Failed to load presets from `{}`: {}", path.display(), err);
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_data_files() {
+        let root = env::temp_dir().join(format!("mouse-configurator-test-{}", std::process::id()));
+        let user_dir = root.join("home/org.pop_os.mouseconfigurator");
+        let system_dirs = [root.join("local"), root.join("share")];
+        let write = |dir: &Path, name: &str, label: &str, binding: &str| {
+            let dir = dir.join("presets");
+            fs::create_dir_all(&dir).unwrap();
+            let data = format!(
+                r#"{{"label": "{}", "presets": [{{"label": "Preset", "binding": "{}"}}]}}"#,
+                label, binding
+            );
+            fs::write(dir.join(name), data).unwrap();
+        };
+        write(&user_dir, "edit.json", "User", "Ctrl+Z");
+        let system_app_dirs = system_dirs
+            .iter()
+            .map(|x| x.join("org.pop_os.mouseconfigurator"))
+            .collect::<Vec<_>>();
+        write(&system_app_dirs[0], "edit.json", "System", "undo");
+        write(&system_app_dirs[0], "invalid.json", "Invalid", "é+A");
+        write(&system_app_dirs[1], "photo.json", "Photo", "Ctrl+0");
+        write(&system_app_dirs[1], "notes.txt", "Ignored", "Ctrl+0");
+        let system_dirs = format!("{}:{}", system_dirs[0].display(), system_dirs[1].display());
+        let dirs = data_dirs_in(&user_dir, Some(&system_dirs), "presets");
+
+        // `edit.json` in the user directory hides the system one
+        let files = files_in(&dirs);
+        let names: Vec<_> = files
+            .iter()
+            .map(|x| x.strip_prefix(&root).unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "home/org.pop_os.mouseconfigurator/presets/edit.json",
+                "local/org.pop_os.mouseconfigurator/presets/invalid.json",
+                "share/org.pop_os.mouseconfigurator/presets/photo.json",
+            ]
+        );
+
+        assert!(load_preset_file(&files[1])
+            .err()
+            .unwrap()
+            .contains("Unrecognized"));
+        // The invalid file is skipped
+        let labels: Vec<_> = load_preset_files(&files)
+            .into_iter()
+            .map(|x| x.label)
+            .collect();
+        assert_eq!(labels, ["User", "Photo"]);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn default_data_dirs() {
+        for system_dirs in [None, Some(""), Some(DEFAULT_DATA_DIRS)] {
+            assert_eq!(
+                data_dirs_in(Path::new("/home/user/app"), system_dirs, "templates"),
+                [
+                    "/home/user/app/templates",
+                    "/usr/local/share/org.pop_os.mouseconfigurator/templates",
+                    "/usr/share/org.pop_os.mouseconfigurator/templates",
+                ]
+                .map(PathBuf::from)
+            );
+        }
+    }
+}
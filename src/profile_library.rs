@@ -0,0 +1,197 @@
+//! Standalone profile files, for sharing a single profile between mice of
+//! the same model, and a library of them in the app data directory.
+//! Templates in the same format can also be installed system-wide; see
+//! `presets`.
+//!
+//! Unlike an exported `MouseConfig`, a profile file has only the settings of
+//! one profile, and the model it is for.
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    bindings::HardwareButton,
+    config::{app_data_dir, write_atomic, Binding, Profile, DEFAULT_DPI},
+    presets,
+};
+
+/// Version of the profile file format
+pub const PROFILE_VERSION: u64 = 1;
+
+fn default_dpi() -> f64 {
+    DEFAULT_DPI
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct ProfileFile {
+    pub version: u64,
+    /// Model of mouse, like the `device` of a `MouseConfig`
+    pub model: String,
+    pub name: Option<String>,
+    #[serde(default)]
+    pub bindings: HashMap<HardwareButton, Binding>,
+    #[serde(default)]
+    pub left_handed: bool,
+    #[serde(default = "default_dpi")]
+    pub dpi: f64,
+}
+
+impl ProfileFile {
+    pub fn new(model: String, profile: &Profile) -> Self {
+        Self {
+            version: PROFILE_VERSION,
+            model,
+            name: profile.name.clone(),
+            bindings: profile.bindings.clone(),
+            left_handed: profile.left_handed,
+            dpi: profile.dpi,
+        }
+    }
+
+    pub fn profile(&self) -> Profile {
+        Profile {
+            name: self.name.clone(),
+            bindings: self.bindings.clone(),
+            left_handed: self.left_handed,
+            dpi: self.dpi,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let data = fs::read_to_string(path).map_err(|x| x.to_string())?;
+        let file: Self = serde_json::from_str(&data).map_err(|x| x.to_string())?;
+        if file.version > PROFILE_VERSION {
+            return Err(format!(
+                "saved by a newer version of Mouse Configurator (format version {})",
+                file.version
+            ));
+        }
+        Ok(file)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let data = serde_json::to_string_pretty(self).unwrap();
+        write_atomic(path, &data).map_err(|x| x.to_string())
+    }
+
+    /// Name of the profile, or the file name for a profile without one
+    pub fn label(&self, path: &Path) -> String {
+        self.name.clone().unwrap_or_else(|| {
+            path.file_stem()
+                .map_or_else(String::new, |x| x.to_string_lossy().into_owned())
+        })
+    }
+}
+
+/// Directory with profiles in the library
+pub fn library_dir() -> PathBuf {
+    let mut path = app_data_dir();
+    path.push("profiles");
+    path
+}
+
+/// Profiles in the library for `model`, sorted by label. Files that can't be
+/// read are skipped.
+pub fn list(model: &str) -> io::Result<Vec<(PathBuf, ProfileFile)>> {
+    let entries = match fs::read_dir(library_dir()) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+    let mut profiles = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().and_then(|x| x.to_str()) != Some("json") {
+            continue;
+        }
+        match ProfileFile::load(&path) {
+            Ok(file) if file.model == model => profiles.push((path, file)),
+            Ok(_) => {}
+            Err(err) => eprintln!("Failed to load `{}`: {}", path.display(), err),
+        }
+    }
+    profiles.sort_by_cached_key(|(path, file)| file.label(path).to_lowercase());
+    Ok(profiles)
+}
+
+/// Profile templates for `model`, from the `templates` data directories
+pub fn templates(model: &str) -> Vec<(PathBuf, ProfileFile)> {
+    presets::data_files("templates")
+        .into_iter()
+        .filter_map(|path| match ProfileFile::load(&path) {
+            Ok(file) if file.model == model => Some((path, file)),
+            Ok(_) => None,
+            Err(err) => {
+                eprintln!("Failed to load `{}`: {}", path.display(), err);
+                None
+            }
+        })
+        .collect()
+}
+
+// Name for a file in the library, from the profile name
+fn file_name(label: &str, n: usize) -> String {
+    let mut name: String = label
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if name.is_empty() {
+        name.push_str("profile");
+    }
+    if n > 0 {
+        name.push_str(&format!("-{}", n));
+    }
+    name + ".json"
+}
+
+/// Add a profile to the library, without replacing an existing file. Returns
+/// the path it was saved to.
+pub fn add(file: &ProfileFile, label: &str) -> Result<PathBuf, String> {
+    let dir = library_dir();
+    let path = (0..)
+        .map(|n| dir.join(file_name(label, n)))
+        .find(|path| !path.exists())
+        .unwrap();
+    file.save(&path)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_file_round_trip() {
+        let mut profile = Profile {
+            name: Some("Photo Editing".to_string()),
+            left_handed: true,
+            dpi: 800.,
+            ..Profile::default()
+        };
+        profile
+            .bindings
+            .insert(HardwareButton::LeftTop, Binding::parse("Ctrl+Z").unwrap());
+        let file = ProfileFile::new("Brain".to_string(), &profile);
+        let json = serde_json::to_value(&file).unwrap();
+        assert_eq!(json["model"], "Brain");
+        assert_eq!(json["name"], "Photo Editing");
+        assert!(json.get("profiles").is_none());
+
+        let file: ProfileFile = serde_json::from_value(json).unwrap();
+        let profile = file.profile();
+        assert_eq!(profile.dpi, 800.);
+        assert!(profile.left_handed);
+        assert!(profile.bindings.contains_key(&HardwareButton::LeftTop));
+        assert_eq!(file_name("Photo Editing", 0), "Photo_Editing.json");
+        assert_eq!(file_name("a/b", 2), "a_b-2.json");
+    }
+}
@@ -0,0 +1,59 @@
+//! Keyboard shortcuts, as a set of modifiers and one key.
+
+use crate::{
+    keycode::{Key, KeyCombination, Modifiers},
+    Op,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct Shortcut {
+    pub modifiers: u8,
+    pub key: u8,
+}
+
+impl Shortcut {
+    /// Recognize a binding of the form generated by `binding`.
+    pub fn from_binding(binding: &[Op]) -> Option<Self> {
+        match binding {
+            [Op::Key {
+                auto_release: true,
+                payload,
+            }] => Self::from_combination(&KeyCombination::from_payload(payload)?),
+            _ => None,
+        }
+    }
+
+    /// Shortcut for a combination of modifiers and one key
+    pub fn from_combination(combination: &KeyCombination) -> Option<Self> {
+        match combination.keys.as_slice() {
+            [key] => Some(Self {
+                modifiers: combination.modifiers.bits(),
+                key: key.usage(),
+            }),
+            _ => None,
+        }
+    }
+
+    fn combination(&self) -> Option<KeyCombination> {
+        let key = Key::from_usage(self.key)?;
+        Some(KeyCombination::new(
+            Modifiers::from_bits(self.modifiers),
+            key,
+        ))
+    }
+
+    pub fn binding(&self) -> Vec<Op> {
+        let payload = match self.combination() {
+            Some(combination) => combination.payload(),
+            None => vec![(self.modifiers as i8).into(), (self.key as i8).into()],
+        };
+        vec![Op::key(true, payload)]
+    }
+
+    pub fn label(&self) -> String {
+        match self.combination() {
+            Some(combination) => combination.to_string(),
+            None => format!("Unknown Key {:#04X}", self.key),
+        }
+    }
+}